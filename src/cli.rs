@@ -0,0 +1,121 @@
+use std::{path::PathBuf, process};
+
+const USAGE: &str = "\
+Usage: ecosystem [OPTIONS]
+
+Options:
+    --headless       Run the simulation without a window or renderer
+    --ticks <N>      Exit after N simulation ticks
+    --seed <SEED>    Seed for the simulation's random number generator
+    --tick-rate <HZ> Simulation ticks per second of simulated time [default: 64]
+    --scenario <PATH>
+                     Scenario file with the initial populations and world parameters
+    --stats <PATH>   Record population statistics and write them as CSV on exit
+    --stats-interval <TICKS>
+                     Ticks between two samples of the statistics [default: 16]
+    --save-snapshot <PATH>
+                     Save a snapshot of the simulation to PATH on exit
+    --load-snapshot <PATH>
+                     Start from a snapshot instead of a scenario
+    --record <PATH>  Record the run to PATH for playback with --replay
+    --replay <PATH>  Play back a recorded run instead of simulating
+    -h, --help       Print this help";
+
+const DEFAULT_TICK_RATE: f64 = 64.0;
+
+/// Options given on the command line.
+pub struct Args {
+    pub headless: bool,
+    pub ticks: Option<u64>,
+    pub seed: Option<u64>,
+    pub tick_rate: f64,
+    pub scenario: Option<PathBuf>,
+    pub stats: Option<PathBuf>,
+    pub stats_interval: Option<u64>,
+    pub save_snapshot: Option<PathBuf>,
+    pub load_snapshot: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+}
+
+impl Args {
+    /// Parse the process arguments, printing usage and exiting on error.
+    pub fn parse() -> Self {
+        match Self::try_parse(std::env::args().skip(1)) {
+            Ok(args) => args,
+            Err(message) => {
+                eprintln!("error: {message}\n\n{USAGE}");
+                process::exit(2);
+            }
+        }
+    }
+
+    fn try_parse(mut iter: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = Args {
+            headless: false,
+            ticks: None,
+            seed: None,
+            tick_rate: DEFAULT_TICK_RATE,
+            scenario: None,
+            stats: None,
+            stats_interval: None,
+            save_snapshot: None,
+            load_snapshot: None,
+            record: None,
+            replay: None,
+        };
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--headless" => args.headless = true,
+                "--ticks" => {
+                    let ticks = parse_value(&arg, iter.next())?;
+                    if ticks == 0 {
+                        return Err("'--ticks' must be at least 1".to_string());
+                    }
+                    args.ticks = Some(ticks);
+                }
+                "--seed" => args.seed = Some(parse_value(&arg, iter.next())?),
+                "--tick-rate" => {
+                    args.tick_rate = parse_value(&arg, iter.next())?;
+                    if !(args.tick_rate.is_finite() && args.tick_rate > 0.0) {
+                        return Err("'--tick-rate' must be a positive number".to_string());
+                    }
+                }
+                "--scenario" => args.scenario = Some(parse_value(&arg, iter.next())?),
+                "--stats" => args.stats = Some(parse_value(&arg, iter.next())?),
+                "--stats-interval" => {
+                    let interval = parse_value(&arg, iter.next())?;
+                    if interval == 0 {
+                        return Err("'--stats-interval' must be at least 1".to_string());
+                    }
+                    args.stats_interval = Some(interval);
+                }
+                "--save-snapshot" => args.save_snapshot = Some(parse_value(&arg, iter.next())?),
+                "--load-snapshot" => args.load_snapshot = Some(parse_value(&arg, iter.next())?),
+                "--record" => args.record = Some(parse_value(&arg, iter.next())?),
+                "--replay" => args.replay = Some(parse_value(&arg, iter.next())?),
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    process::exit(0);
+                }
+                _ => return Err(format!("unknown argument '{arg}'")),
+            }
+        }
+
+        if args.headless && args.replay.is_some() {
+            return Err(
+                "'--replay' needs a window and cannot be used with '--headless'".to_string(),
+            );
+        }
+
+        Ok(args)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for '{flag}'"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for '{flag}'"))
+}
@@ -1,18 +1,128 @@
+// Bevy system parameters are complex types by nature.
+#![allow(clippy::type_complexity)]
+
+mod cli;
 mod menu;
 mod simulation;
 mod state;
 
+use std::{process, time::Duration};
+
 use state::AppState;
 
-use bevy::prelude::*;
+use bevy::{
+    app::ScheduleRunnerPlugin, log::LogPlugin, prelude::*, state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
-        .init_state::<AppState>()
-        .add_systems(Startup, setup)
-        .add_plugins((menu::menu_plugin, simulation::simulation_plugin))
-        .run();
+    let args = cli::Args::parse();
+
+    let fixed_time = Time::<Fixed>::from_hz(args.tick_rate);
+
+    let mut app = App::new();
+    app.insert_resource(fixed_time);
+
+    if args.headless {
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+            StatesPlugin,
+            LogPlugin::default(),
+        ))
+        // Advance exactly one fixed tick per frame so that headless runs are
+        // not throttled to wall-clock time.
+        .insert_resource(TimeUpdateStrategy::ManualDuration(fixed_time.timestep()))
+        .insert_state(AppState::Simulation);
+    } else {
+        app.add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+            .add_systems(Startup, setup)
+            .add_plugins((
+                menu::menu_plugin,
+                simulation::presentation_plugin,
+                simulation::playback_plugin,
+            ));
+
+        if args.replay.is_some() {
+            app.insert_state(AppState::Replay);
+        } else {
+            app.init_state::<AppState>();
+        }
+    }
+
+    let registry = match simulation::SpeciesRegistry::load(&simulation::species_dir()) {
+        Ok(registry) => registry,
+        Err(err) => {
+            eprintln!("error: {err}");
+            process::exit(1);
+        }
+    };
+
+    if let Some(path) = &args.scenario {
+        match simulation::Scenario::load(path, &registry) {
+            Ok(scenario) => app.insert_resource(scenario),
+            Err(err) => {
+                eprintln!("error: {err}");
+                process::exit(1);
+            }
+        };
+    } else {
+        // The built-in scenario names species that may have been renamed or
+        // removed from the species directory.
+        let scenario = simulation::Scenario::default();
+        if let Err(err) = scenario.validate(&registry) {
+            eprintln!("error: default scenario: {err}");
+            process::exit(1);
+        }
+        app.insert_resource(scenario);
+    }
+
+    if let Some(path) = &args.load_snapshot {
+        match simulation::Snapshot::load(path, &registry) {
+            Ok(snapshot) => app.insert_resource(snapshot),
+            Err(err) => {
+                eprintln!("error: {err}");
+                process::exit(1);
+            }
+        };
+    }
+
+    if let Some(path) = &args.replay {
+        match simulation::Replay::load(path, &registry) {
+            Ok(replay) => app.insert_resource(replay),
+            Err(err) => {
+                eprintln!("error: {err}");
+                process::exit(1);
+            }
+        };
+    }
+
+    app.insert_resource(registry);
+
+    if let Some(seed) = args.seed {
+        app.insert_resource(simulation::SimSeed(seed));
+    }
+
+    if let Some(path) = args.stats {
+        app.insert_resource(simulation::StatsFile(path));
+    }
+
+    if let Some(path) = args.record {
+        app.insert_resource(simulation::ReplayFile(path));
+    }
+
+    if let Some(path) = args.save_snapshot {
+        app.insert_resource(simulation::SnapshotFile(path));
+    }
+
+    if let Some(interval) = args.stats_interval {
+        app.insert_resource(simulation::StatsRecorder::new(interval));
+    }
+
+    if let Some(ticks) = args.ticks {
+        app.insert_resource(simulation::TickLimit(ticks));
+    }
+
+    app.add_plugins(simulation::simulation_plugin).run();
 }
 
 fn setup(mut commands: Commands) {
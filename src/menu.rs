@@ -1,6 +1,11 @@
+use std::path::{Path, PathBuf};
+
 use bevy::prelude::*;
 
-use crate::state::AppState;
+use crate::{
+    simulation::{list_scenarios, list_snapshots, Scenario, Snapshot, SpeciesRegistry},
+    state::AppState,
+};
 
 const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
@@ -9,6 +14,21 @@ const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
 #[derive(Component)]
 struct MenuComponent;
 
+/// Starts the simulation when pressed.
+#[derive(Component)]
+enum MenuButton {
+    /// Keep the current `Scenario`.
+    Play,
+    /// Load a scenario file first.
+    Scenario(PathBuf),
+    /// Restore a snapshot file instead of setting up a scenario.
+    Snapshot(PathBuf),
+}
+
+/// Text showing why a scenario or snapshot could not be loaded.
+#[derive(Component)]
+struct MenuMessage;
+
 pub fn menu_plugin(app: &mut App) {
     app.add_systems(OnEnter(AppState::Menu), setup)
         .add_systems(Update, button_system.run_if(in_state(AppState::Menu)))
@@ -16,17 +36,34 @@ pub fn menu_plugin(app: &mut App) {
 }
 
 fn button_system(
+    mut commands: Commands,
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
+        (&Interaction, &mut BackgroundColor, &MenuButton),
         (Changed<Interaction>, With<Button>),
     >,
+    mut message: Single<&mut Text, With<MenuMessage>>,
     mut state: ResMut<NextState<AppState>>,
+    registry: Res<SpeciesRegistry>,
 ) {
-    for (interaction, mut color) in &mut interaction_query {
+    for (interaction, mut color, menu_button) in &mut interaction_query {
         match *interaction {
-            Interaction::Pressed => {
-                state.set(AppState::Simulation);
-            }
+            Interaction::Pressed => match menu_button {
+                MenuButton::Play => state.set(AppState::Simulation),
+                MenuButton::Scenario(path) => match Scenario::load(path, &registry) {
+                    Ok(scenario) => {
+                        commands.insert_resource(scenario);
+                        state.set(AppState::Simulation);
+                    }
+                    Err(err) => message.0 = err.to_string(),
+                },
+                MenuButton::Snapshot(path) => match Snapshot::load(path, &registry) {
+                    Ok(snapshot) => {
+                        commands.insert_resource(snapshot);
+                        state.set(AppState::Simulation);
+                    }
+                    Err(err) => message.0 = err.to_string(),
+                },
+            },
             Interaction::Hovered => {
                 *color = HOVERED_BUTTON.into();
             }
@@ -38,38 +75,74 @@ fn button_system(
 }
 
 fn setup(mut commands: Commands) {
-    commands.spawn((
-        Node {
-            // center button
-            width: Val::Percent(100.),
-            height: Val::Percent(100.),
-            justify_content: JustifyContent::Center,
-            align_items: AlignItems::Center,
-            ..default()
-        },
-        MenuComponent,
-        children![(
-            Button,
+    commands
+        .spawn((
             Node {
-                width: Val::Px(150.),
-                height: Val::Px(65.),
-                // horizontally center child text
+                // center buttons
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
                 justify_content: JustifyContent::Center,
-                // vertically center child text
                 align_items: AlignItems::Center,
+                row_gap: Val::Px(10.),
                 ..default()
             },
-            BackgroundColor(NORMAL_BUTTON),
-            children![(
-                Text::new("Play"),
+            MenuComponent,
+        ))
+        .with_children(|parent| {
+            parent.spawn(button("Play", 33.0, MenuButton::Play));
+
+            for path in list_scenarios() {
+                let label = file_stem(&path);
+                parent.spawn(button(&label, 22.0, MenuButton::Scenario(path)));
+            }
+
+            for path in list_snapshots() {
+                let label = format!("Load {}", file_stem(&path));
+                parent.spawn(button(&label, 22.0, MenuButton::Snapshot(path)));
+            }
+
+            parent.spawn((
+                MenuMessage,
+                Text::new(""),
                 TextFont {
-                    font_size: 33.0,
+                    font_size: 18.0,
                     ..default()
                 },
-                TextColor(Color::srgb(0.9, 0.9, 0.9)),
-            )],
+                TextColor(Color::srgb(0.9, 0.3, 0.3)),
+            ));
+        });
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn button(label: &str, font_size: f32, menu_button: MenuButton) -> impl Bundle {
+    (
+        Button,
+        menu_button,
+        Node {
+            width: Val::Px(250.),
+            height: Val::Px(65.),
+            // horizontally center child text
+            justify_content: JustifyContent::Center,
+            // vertically center child text
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(NORMAL_BUTTON),
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size,
+                ..default()
+            },
+            TextColor(Color::srgb(0.9, 0.9, 0.9)),
         )],
-    ));
+    )
 }
 
 fn exit(query: Query<Entity, With<MenuComponent>>, mut commands: Commands) {
@@ -1,150 +1,245 @@
 mod berry;
+mod brain;
+mod camera;
+mod collision;
 mod constants;
+mod corpse;
+mod genome;
+mod hud;
 mod hunger;
+mod inspect;
 mod motion;
+mod obstacle;
+mod playback;
+mod presentation;
+mod replay;
+mod reproduction;
+mod rng;
+mod scenario;
+mod senses;
+mod snapshot;
+mod spatial;
+mod species;
+mod speed;
+mod stats;
+mod steering;
+mod terrain;
 mod unit;
 
 use std::f32::consts::PI;
 
 use crate::{
-    simulation::{constants::*, unit::fernworm, unit::zyrthid},
+    simulation::{constants::*, species::Species},
     state::AppState,
 };
 
 use bevy::{
-    math::bounding::{Aabb2d, IntersectsVolume},
+    math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume},
     prelude::*,
 };
 use rand::Rng;
 
+pub use playback::playback_plugin;
+pub use presentation::presentation_plugin;
+pub use replay::{Replay, ReplayFile};
+pub use rng::SimSeed;
+pub use scenario::{list_scenarios, Scenario};
+pub use snapshot::{list_snapshots, Snapshot, SnapshotFile};
+pub use species::{species_dir, SpeciesRegistry};
+pub use stats::{StatsFile, StatsRecorder};
+
 #[derive(Resource)]
 struct SimData {
+    /// Number of ticks simulated so far.
+    tick: u64,
+    /// Berries that do not belong to a bush.
     num_berries: u64,
     max_berries: u64,
+    /// Id of the next unit to spawn.
+    next_unit_id: u64,
+}
+
+impl SimData {
+    /// Lineage of a unit spawned now, as offspring of `parent` if given.
+    fn new_lineage(&mut self, parent: Option<&unit::Lineage>) -> unit::Lineage {
+        let id = self.next_unit_id;
+        self.next_unit_id += 1;
+
+        unit::Lineage {
+            id,
+            parent: parent.map(|parent| parent.id),
+            generation: parent.map_or(0, |parent| parent.generation + 1),
+            born: self.tick,
+        }
+    }
 }
 
+/// Number of simulation ticks after which the app exits.
+#[derive(Resource)]
+pub struct TickLimit(pub u64);
+
 #[derive(Component)]
 struct SimulationComponent;
 
+/// Core simulation logic. Does not depend on any rendering or windowing
+/// plugins, so it can be run headless on top of `MinimalPlugins`.
+///
+/// All simulation systems run in `FixedUpdate` in a fixed order, so that a
+/// run only depends on its `SimSeed` and not on the frame rate.
 pub fn simulation_plugin(app: &mut App) {
-    app.add_systems(OnEnter(AppState::Simulation), setup)
-        .add_systems(OnExit(AppState::Simulation), exit)
-        .add_systems(
-            Update,
+    app.add_systems(
+        OnEnter(AppState::Simulation),
+        (
+            (
+                setup.run_if(not(resource_exists::<Snapshot>)),
+                snapshot::restore_snapshot,
+                terrain::load_terrain,
+                obstacle::build_nav_grid,
+                replay::start_recording.run_if(resource_exists::<ReplayFile>),
+            )
+                .chain(),
+            stats::reset_stats,
+        ),
+    )
+    .add_systems(
+        OnExit(AppState::Simulation),
+        (stats::write_stats_on_exit, replay::stop_recording, exit),
+    )
+    .add_systems(
+        FixedUpdate,
+        (
+            spatial::index_positions,
+            brain::use_brains,
             (
-                handle_input.run_if(in_state(AppState::Simulation)),
-                motion::apply_velocity.run_if(in_state(AppState::Simulation)),
-                motion::apply_rotation.run_if(in_state(AppState::Simulation)),
-                hunger::hunger_drain.run_if(in_state(AppState::Simulation)),
-                berry::spawn_berries.run_if(in_state(AppState::Simulation)),
-                motion::update_velocity.run_if(in_state(AppState::Simulation)),
-                motion::repel_bodies.run_if(in_state(AppState::Simulation)),
-                unit::fernworm::use_brain.run_if(in_state(AppState::Simulation)),
-                zyrthid::use_brain.run_if(in_state(AppState::Simulation)),
-                (
-                    unit::fernworm::eat_berries.run_if(in_state(AppState::Simulation)),
-                    zyrthid::eat_fernworms.run_if(in_state(AppState::Simulation)),
-                    hunger::kill_starved_units.run_if(in_state(AppState::Simulation)),
-                    unit::kill_units.run_if(in_state(AppState::Simulation)),
-                )
-                    .chain(),
-            ),
+                motion::update_velocity,
+                motion::apply_velocity,
+                // Collisions are resolved where the units have moved to.
+                spatial::index_positions,
+                collision::resolve_collisions,
+                obstacle::resolve_obstacles,
+                motion::apply_boundary,
+                motion::apply_rotation,
+            )
+                .chain(),
+            // Index again now that the units have moved.
+            spatial::index_positions,
+            hunger::hunger_drain,
+            unit::eat_berries,
+            unit::eat_prey,
+            unit::eat_carrion,
+            hunger::kill_starved_units,
+            unit::kill_units,
+            corpse::decay_corpses,
+            reproduction::reproduce,
+            berry::spawn_berries,
+            berry::grow_berries,
+            stats::record_stats,
+            replay::record_frame.run_if(resource_exists::<replay::ReplayRecorder>),
+            advance_tick,
+            count_ticks.run_if(resource_exists::<TickLimit>),
+        )
+            .chain()
+            .run_if(in_state(AppState::Simulation)),
+    )
+    .add_systems(
+        Update,
+        (
+            stats::write_stats_on_request,
+            snapshot::save_snapshot_on_request,
         )
-        .add_observer(on_drag_move_screen)
-        .insert_resource(SimData {
-            num_berries: 0,
-            max_berries: MAX_BERRIES,
-        })
-        .add_event::<unit::DeathEvent>();
+            .run_if(in_state(AppState::Simulation)),
+    )
+    .add_systems(
+        Last,
+        (
+            stats::write_stats_on_app_exit,
+            snapshot::save_snapshot_on_app_exit,
+            replay::flush_recording_on_app_exit,
+        )
+            .run_if(in_state(AppState::Simulation)),
+    )
+    .init_resource::<Scenario>()
+    .init_resource::<spatial::SpatialIndex>()
+    .init_resource::<StatsRecorder>()
+    .insert_resource(SimData {
+        tick: 0,
+        num_berries: 0,
+        max_berries: 0,
+        next_unit_id: 0,
+    })
+    .add_event::<unit::DeathEvent>()
+    .add_event::<unit::BirthEvent>()
+    .add_event::<stats::WriteStats>()
+    .add_event::<snapshot::SaveSnapshot>();
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut game_data: ResMut<SimData>) {
-    let mut rng = rand::thread_rng();
-
-    commands.spawn((
-        SimulationComponent,
-        Sprite {
-            image: asset_server.load("sprites/forest.png"),
-            custom_size: Some(Vec2::new(SCREEN_WIDTH, SCREEN_HEIGHT)),
-            ..default()
-        },
-    ));
-
-    for _ in 0..NUM_FERNWORMS {
-        let init_pos = Vec3::new(
-            rng.gen_range(PLAYABLE_AREA_X0..PLAYABLE_AREA_X1) as f32,
-            rng.gen_range(PLAYABLE_AREA_Y0..PLAYABLE_AREA_Y1) as f32,
-            2.0,
-        );
-
-        commands.spawn((
-            SimulationComponent,
-            fernworm::Fernworm,
-            unit::UnitType::Fernworm,
-            motion::Rotation(0.0),
-            Sprite {
-                image: asset_server.load("sprites/fernworm.png"),
-                custom_size: Some(Vec2::new(FERNWORM_RENDER_WIDTH, FERNWORM_RENDER_HEIGHT)),
-                ..default()
-            },
-            Transform {
-                translation: init_pos,
-                ..default()
-            },
-            motion::MovingBody {
-                curr_velocity: Vec3::ZERO,
-                max_speed: 200.0,
-                curr_acceleration: Vec3::ZERO,
-                max_acceleration: 1000.0,
-            },
-            hunger::Hunger {
-                curr_fullness: 100.0,
-                max_fullness: 100.0,
-                drain_per_unit_traveled: 25.0 / 200.0,
-                last_sampled_pos: init_pos,
-            },
-            motion::TargetPoint(None),
-        ));
+fn setup(
+    mut commands: Commands,
+    mut game_data: ResMut<SimData>,
+    seed: Option<Res<rng::SimSeed>>,
+    scenario: Res<Scenario>,
+    registry: Res<SpeciesRegistry>,
+) {
+    let seed = match seed {
+        Some(seed) => *seed,
+        None => scenario
+            .seed
+            .map(rng::SimSeed)
+            .unwrap_or_else(rng::SimSeed::random),
+    };
+    let mut rng = rng::SimRng::from_seed(seed);
+
+    game_data.tick = 0;
+    game_data.num_berries = 0;
+    game_data.max_berries = scenario.world.max_berries;
+    game_data.next_unit_id = 0;
+
+    for (name, config) in &scenario.species {
+        // Scenarios are validated against the registry when loaded.
+        let id = registry.id(name).expect("scenario has an unknown species");
+        let species = registry.get(id);
+
+        for _ in 0..config.count {
+            let position = scenario.world.random_position(&mut rng);
+
+            let brain = config.brain(species, &mut rng);
+            let genome = genome::Genome {
+                brain_weights: brain.initial_weights(&mut rng),
+                ..config.genome(species)
+            };
+            let senses = config.senses(species, &genome);
+
+            unit::spawn_unit(
+                &mut commands,
+                &registry,
+                unit::UnitSpawn {
+                    species: id,
+                    position,
+                    genome,
+                    fullness: config.initial_fullness(species),
+                    brain,
+                    senses,
+                    lineage: game_data.new_lineage(None),
+                },
+            );
+        }
     }
 
-    for _ in 0..NUM_ZYRTHIDS {
-        let init_pos = Vec3::new(
-            rng.gen_range(PLAYABLE_AREA_X0..PLAYABLE_AREA_X1) as f32,
-            rng.gen_range(PLAYABLE_AREA_Y0..PLAYABLE_AREA_Y1) as f32,
-            1.5,
-        );
-
-        commands.spawn((
-            SimulationComponent,
-            zyrthid::Zyrthid,
-            unit::UnitType::Zyrthid,
-            motion::Rotation(0.0),
-            Sprite {
-                image: asset_server.load("sprites/zyrthid.png"),
-                custom_size: Some(Vec2::new(ZYRTHID_RENDER_WIDTH, ZYRTHID_RENDER_HEIGHT)),
-                ..default()
-            },
-            Transform {
-                translation: init_pos,
-                ..default()
-            },
-            motion::MovingBody {
-                curr_velocity: Vec3::ZERO,
-                max_speed: 150.0,
-                curr_acceleration: Vec3::ZERO,
-                max_acceleration: 1000.0,
-            },
-            hunger::Hunger {
-                curr_fullness: 200.0,
-                max_fullness: 200.0,
-                drain_per_unit_traveled: 15.0 / 150.0,
-                last_sampled_pos: init_pos,
-            },
-            motion::TargetPoint(None),
-        ));
+    for patch in &scenario.world.patches {
+        for _ in 0..patch.bushes {
+            let position =
+                berry::position_near(patch.center(), patch.radius, &scenario.world, &mut rng);
+            berry::spawn_bush(
+                &mut commands,
+                position,
+                patch.bush(),
+                &scenario.world,
+                &mut rng,
+            );
+        }
     }
 
-    game_data.num_berries = 0;
+    commands.insert_resource(rng);
 }
 
 fn exit(query: Query<Entity, With<SimulationComponent>>, mut commands: Commands) {
@@ -153,22 +248,14 @@ fn exit(query: Query<Entity, With<SimulationComponent>>, mut commands: Commands)
     }
 }
 
-fn handle_input(keyboard_input: Res<ButtonInput<KeyCode>>, mut state: ResMut<NextState<AppState>>) {
-    if keyboard_input.just_pressed(KeyCode::Escape) {
-        state.set(AppState::Menu);
-    }
+fn advance_tick(mut game_data: ResMut<SimData>) {
+    game_data.tick += 1;
 }
 
-fn on_drag_move_screen(
-    drag: Trigger<Pointer<Drag>>,
-    mut camera: Single<&mut Transform, With<Camera2d>>,
-    state: Res<State<AppState>>,
-) {
-    // Currently no way to only trigger the observer in one state. We have to explicitly check the state here.
-    if *state != AppState::Simulation {
-        return;
+/// Exit once the tick that was just simulated is the last one allowed.
+fn count_ticks(mut limit: ResMut<TickLimit>, mut app_exit: EventWriter<AppExit>) {
+    limit.0 = limit.0.saturating_sub(1);
+    if limit.0 == 0 {
+        app_exit.write(AppExit::Success);
     }
-
-    camera.translation.x -= drag.delta.x;
-    camera.translation.y += drag.delta.y;
 }
@@ -1,38 +1,153 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::simulation::*;
 
+/// A berry lying in the world. Berries that grew on a bush belong to it, so
+/// that the bush knows to grow them back once they are eaten.
 #[derive(Component)]
-pub struct Berry;
+pub struct Berry {
+    /// Fullness gained by eating it.
+    pub fullness: f32,
+    pub bush: Option<Entity>,
+}
 
-pub fn spawn_berries(
-    mut game_data: ResMut<SimData>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-) {
-    let mut rng = rand::thread_rng();
+/// A bush that grows berries around itself up to its capacity, and grows them
+/// back gradually after they are eaten.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Bush {
+    /// Berries grown per second while below capacity.
+    pub regrowth_rate: f32,
+    pub capacity: u32,
+    /// Fullness gained by eating one of its berries.
+    pub berry_yield: f32,
+    /// Berries of this bush that have not been eaten yet.
+    pub berries: u32,
+    /// Progress towards the next berry, where 1 is fully grown.
+    pub growth: f32,
+}
 
-    for _ in game_data.num_berries..game_data.max_berries {
-        let init_pos_berry = Vec3::new(
-            rng.gen_range(PLAYABLE_AREA_X0..PLAYABLE_AREA_X1) as f32,
-            rng.gen_range(PLAYABLE_AREA_Y0..PLAYABLE_AREA_Y1) as f32,
-            1.0,
-        );
+/// Random position within `radius` of `center` that is inside the world's
+/// bounds and outside its obstacles.
+pub fn position_near(
+    center: Vec2,
+    radius: f32,
+    world: &scenario::WorldConfig,
+    rng: &mut rng::SimRng,
+) -> Vec2 {
+    world.free_position(rng, |rng| {
+        let angle = rng.0.gen_range(0.0..2.0 * PI);
+        let distance = rng.0.gen_range(0.0..=radius);
+
+        world.contain(center + distance * Vec2::from_angle(angle))
+    })
+}
 
-        commands.spawn((
+pub fn spawn_berry(commands: &mut Commands, position: Vec2, berry: Berry) {
+    commands.spawn((
+        SimulationComponent,
+        berry,
+        Transform {
+            translation: position.extend(1.0),
+            ..default()
+        },
+    ));
+}
+
+/// Spawn a bush together with a full load of berries.
+pub fn spawn_bush(
+    commands: &mut Commands,
+    position: Vec2,
+    mut bush: Bush,
+    world: &scenario::WorldConfig,
+    rng: &mut rng::SimRng,
+) {
+    bush.berries = bush.capacity;
+    let berry_yield = bush.berry_yield;
+    let capacity = bush.capacity;
+
+    let entity = commands
+        .spawn((
             SimulationComponent,
-            Berry,
-            Sprite {
-                image: asset_server.load("sprites/berry.png"),
-                custom_size: Some(Vec2::new(BERRY_RENDER_WIDTH, BERRY_RENDER_HEIGHT)),
-                ..default()
-            },
+            bush,
             Transform {
-                translation: init_pos_berry,
+                translation: position.extend(BUSH_DEPTH),
                 ..default()
             },
-        ));
+        ))
+        .id();
+
+    for _ in 0..capacity {
+        spawn_berry(
+            commands,
+            position_near(position, BUSH_RADIUS, world, rng),
+            Berry {
+                fullness: berry_yield,
+                bush: Some(entity),
+            },
+        );
+    }
+}
+
+/// Top the berries that lie at random positions up to the scenario's maximum.
+/// Berries that grew from corpses come on top of the maximum.
+pub fn spawn_berries(
+    mut game_data: ResMut<SimData>,
+    mut commands: Commands,
+    mut rng: ResMut<rng::SimRng>,
+    scenario: Res<Scenario>,
+) {
+    while game_data.num_berries < game_data.max_berries {
+        let position = scenario.world.random_position(&mut rng);
+        spawn_berry(
+            &mut commands,
+            position,
+            Berry {
+                fullness: BERRY_FULLNESS_GAIN,
+                bush: None,
+            },
+        );
+        game_data.num_berries += 1;
     }
+}
+
+/// Let bushes grow back the berries that were eaten from them, as fast as
+/// the terrain they stand on allows.
+pub fn grow_berries(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Transform, &mut Bush)>,
+    mut rng: ResMut<rng::SimRng>,
+    scenario: Res<Scenario>,
+    terrain: Res<terrain::Terrain>,
+    time: Res<Time<Fixed>>,
+) {
+    for (entity, transform, mut bush) in query.iter_mut() {
+        // A full bush does not start on the next berry until one is eaten.
+        if bush.berries >= bush.capacity {
+            bush.growth = 0.0;
+            continue;
+        }
 
-    game_data.num_berries = game_data.max_berries;
+        let growth = terrain.effects_at(transform.translation.truncate()).growth;
+        bush.growth += bush.regrowth_rate * growth * time.delta_secs();
+        while bush.growth >= 1.0 && bush.berries < bush.capacity {
+            bush.growth -= 1.0;
+            bush.berries += 1;
+
+            let position = position_near(
+                transform.translation.truncate(),
+                BUSH_RADIUS,
+                &scenario.world,
+                &mut rng,
+            );
+            spawn_berry(
+                &mut commands,
+                position,
+                Berry {
+                    fullness: bush.berry_yield,
+                    bush: Some(entity),
+                },
+            );
+        }
+    }
 }
@@ -0,0 +1,554 @@
+pub mod neural;
+
+use bevy::prelude::*;
+use rand_distr::{Distribution, StandardNormal};
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::*;
+
+/// Distance ahead of a wandering unit that its target point is placed at.
+const WANDER_DISTANCE: f32 = 100.0;
+
+/// Distance from a unit with a neural network brain that its target point is
+/// placed at when the network outputs full steering.
+const NEURAL_STEERING_DISTANCE: f32 = 100.0;
+
+/// Distance from a unit with a steering brain that its target point is placed
+/// at, in the direction that it accelerates in.
+const STEERING_DISTANCE: f32 = 100.0;
+
+/// Distance within which units flock with others of their species.
+const FLOCK_RADIUS: f32 = 100.0;
+
+/// Distance ahead of a unit within which it avoids other bodies and
+/// obstacles.
+const AVOIDANCE_DISTANCE: f32 = 80.0;
+
+/// Standard deviation of the heading drift of units that search for food they
+/// cannot see, in radians per square root second.
+const SEARCH_TURN_RATE: f32 = 1.0;
+
+/// Decides how a unit accelerates by writing its `motion::MovingBody`'s
+/// acceleration, and where it is heading by writing its `motion::TargetPoint`.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub enum Brain {
+    /// Chase the nearest food in sight, and wander around looking for some
+    /// when there is none.
+    Seeker {
+        /// Heading that the unit searches in.
+        heading: f32,
+    },
+    /// Roam around with a randomly drifting heading.
+    Wanderer {
+        /// Standard deviation of the heading drift, in radians per square root
+        /// second.
+        turn_rate: f32,
+        heading: f32,
+    },
+    /// Visit a list of points in order, starting over after the last one.
+    Scripted {
+        waypoints: Vec<Vec2>,
+        /// Distance at which a waypoint counts as visited.
+        arrival_radius: f32,
+        next: usize,
+    },
+    /// Steer with a feedforward network with one hidden layer. Its weights are
+    /// stored in the unit's `genome::Genome`, so they are inherited.
+    Neural { hidden: usize },
+    /// Blend weighted steering behaviours, such as chasing food while evading
+    /// predators.
+    Steering {
+        behaviours: Vec<(f32, steering::Behaviour)>,
+        /// Heading that `steering::Behaviour::Wander` drifts.
+        heading: f32,
+    },
+}
+
+/// Food that a unit's brain last steered towards, if any. Only kept for
+/// inspecting units, so it is not part of snapshots.
+#[derive(Component, Default)]
+pub struct Target(pub Option<Entity>);
+
+/// A brain as written in species and scenario files.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub enum BrainConfig {
+    #[default]
+    Seeker,
+    Wanderer {
+        turn_rate: f32,
+    },
+    Scripted {
+        waypoints: Vec<(f32, f32)>,
+        arrival_radius: f32,
+    },
+    Neural {
+        hidden: usize,
+    },
+    Steering {
+        behaviours: Vec<(f32, steering::Behaviour)>,
+    },
+}
+
+impl BrainConfig {
+    pub fn build(&self, rng: &mut rng::SimRng) -> Brain {
+        match self {
+            BrainConfig::Seeker => Brain::Seeker {
+                heading: rng.0.gen_range(0.0..2.0 * PI),
+            },
+            BrainConfig::Wanderer { turn_rate } => Brain::Wanderer {
+                turn_rate: *turn_rate,
+                heading: rng.0.gen_range(0.0..2.0 * PI),
+            },
+            BrainConfig::Scripted {
+                waypoints,
+                arrival_radius,
+            } => Brain::Scripted {
+                waypoints: waypoints.iter().map(|&(x, y)| Vec2::new(x, y)).collect(),
+                arrival_radius: *arrival_radius,
+                next: 0,
+            },
+            BrainConfig::Neural { hidden } => Brain::Neural { hidden: *hidden },
+            BrainConfig::Steering { behaviours } => Brain::Steering {
+                behaviours: behaviours.clone(),
+                heading: rng.0.gen_range(0.0..2.0 * PI),
+            },
+        }
+    }
+
+    /// Returns the name of the first invalid field and why, if any.
+    pub fn invalid_field(&self) -> Option<(String, &'static str)> {
+        match self {
+            BrainConfig::Seeker => None,
+            BrainConfig::Wanderer { turn_rate } => (!(turn_rate.is_finite() && *turn_rate >= 0.0))
+                .then(|| ("turn_rate".to_string(), "must be a non-negative number")),
+            BrainConfig::Scripted {
+                waypoints,
+                arrival_radius,
+            } => {
+                if waypoints.is_empty() {
+                    Some(("waypoints".to_string(), "must not be empty"))
+                } else if !(arrival_radius.is_finite() && *arrival_radius > 0.0) {
+                    Some(("arrival_radius".to_string(), "must be a positive number"))
+                } else {
+                    None
+                }
+            }
+            BrainConfig::Neural { hidden } => {
+                (*hidden == 0).then(|| ("hidden".to_string(), "must be at least 1"))
+            }
+            BrainConfig::Steering { behaviours } => {
+                if behaviours.is_empty() {
+                    return Some(("behaviours".to_string(), "must not be empty"));
+                }
+                behaviours
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, (weight, behaviour))| {
+                        if !(weight.is_finite() && *weight > 0.0) {
+                            Some((format!("behaviours[{i}].0"), "must be a positive number"))
+                        } else {
+                            behaviour.invalid_field().map(|(field, reason)| {
+                                (format!("behaviours[{i}].1.{field}"), reason)
+                            })
+                        }
+                    })
+            }
+        }
+    }
+}
+
+impl Brain {
+    /// Weights a genome must carry for this brain when it has not inherited
+    /// any.
+    pub fn initial_weights(&self, rng: &mut rng::SimRng) -> Vec<f32> {
+        match self {
+            Brain::Neural { hidden } => neural::random_weights(*hidden, &mut rng.0),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// What a unit can sense around itself.
+struct Surroundings<'a> {
+    entity: Entity,
+    pos: Vec2,
+    rotation: f32,
+    species: &'a Species,
+    senses: &'a senses::Senses,
+    index: &'a spatial::SpatialIndex,
+    world: &'a scenario::WorldConfig,
+    nav: &'a obstacle::NavGrid,
+}
+
+impl Surroundings<'_> {
+    /// Whether the unit sees what is at `offset` from it.
+    fn sees(&self, offset: Vec2) -> bool {
+        self.senses
+            .sees(self.world, self.pos, self.rotation, offset)
+    }
+
+    /// Offset from the unit to `entry`.
+    fn offset<T>(&self, entry: &spatial::SpatialEntry<T>) -> Vec2 {
+        self.world.offset(self.pos, entry.position.truncate())
+    }
+
+    /// The nearest food in the unit's diet in sight, heading along the unit's
+    /// `route` if obstacles are in the way.
+    fn food(&self, route: &mut obstacle::Route) -> Option<(Entity, steering::Neighbour)> {
+        let diet = &self.species.diet;
+
+        let berry = self
+            .index
+            .berries
+            .nearest_wrapped(self.world, self.pos, self.senses.vision_radius, |berry| {
+                diet.berries && self.sees(self.offset(berry))
+            })
+            .map(|(berry, offset)| {
+                let neighbour = steering::Neighbour {
+                    offset,
+                    velocity: Vec2::ZERO,
+                    radius: berry.radius,
+                };
+                (berry.entity, neighbour)
+            });
+        let prey = self
+            .index
+            .units
+            .nearest_wrapped(self.world, self.pos, self.senses.vision_radius, |food| {
+                food.entity != self.entity
+                    && diet.prey.contains(&food.data.species)
+                    && self.sees(self.offset(food))
+            })
+            .map(|(prey, offset)| (prey.entity, neighbour(&prey, offset)));
+
+        let corpse = self
+            .index
+            .corpses
+            .nearest_wrapped(self.world, self.pos, self.senses.vision_radius, |corpse| {
+                diet.carrion && self.sees(self.offset(corpse))
+            })
+            .map(|(corpse, offset)| {
+                let neighbour = steering::Neighbour {
+                    offset,
+                    velocity: Vec2::ZERO,
+                    radius: corpse.radius,
+                };
+                (corpse.entity, neighbour)
+            });
+
+        [berry, prey, corpse]
+            .into_iter()
+            .flatten()
+            .min_by(|(_, a), (_, b)| {
+                a.offset
+                    .length_squared()
+                    .total_cmp(&b.offset.length_squared())
+            })
+            .map(|(food, food_body)| (food, self.detour(route, food_body)))
+    }
+
+    /// Where to head for on the way to `target`, which is the next point of a
+    /// route around the obstacles if they are in the way.
+    fn detour(
+        &self,
+        route: &mut obstacle::Route,
+        target: steering::Neighbour,
+    ) -> steering::Neighbour {
+        let offset = obstacle::route(self.world, self.nav, route, self.pos, target.offset);
+        if offset == target.offset {
+            return target;
+        }
+
+        steering::Neighbour {
+            offset,
+            velocity: Vec2::ZERO,
+            radius: 0.0,
+        }
+    }
+
+    /// The nearest unit in sight that preys on the unit.
+    fn predator(&self) -> Option<steering::Neighbour> {
+        self.index
+            .units
+            .nearest_wrapped(self.world, self.pos, self.senses.vision_radius, |other| {
+                other.entity != self.entity
+                    && self.species.predators.contains(&other.data.species)
+                    && self.sees(self.offset(other))
+            })
+            .map(|(predator, offset)| neighbour(&predator, offset))
+    }
+
+    /// Other units in sight and closer than `radius` that are accepted by
+    /// `filter`.
+    fn units_within(
+        &self,
+        radius: f32,
+        filter: impl Fn(&spatial::UnitData) -> bool,
+    ) -> Vec<steering::Neighbour> {
+        self.index
+            .units
+            .within_wrapped(self.world, self.pos, radius)
+            .filter(|(other, offset)| {
+                other.entity != self.entity && filter(&other.data) && self.sees(*offset)
+            })
+            .map(|(other, offset)| neighbour(other, offset))
+            .collect()
+    }
+}
+
+fn neighbour(
+    entry: &spatial::SpatialEntry<spatial::UnitData>,
+    offset: Vec2,
+) -> steering::Neighbour {
+    steering::Neighbour {
+        offset,
+        velocity: entry.data.velocity,
+        radius: entry.radius,
+    }
+}
+
+/// Let every unit's brain choose how to accelerate.
+pub fn use_brains(
+    mut unit_query: Query<(
+        Entity,
+        &Transform,
+        &motion::Rotation,
+        &unit::UnitType,
+        &genome::Genome,
+        &senses::Senses,
+        &hunger::Hunger,
+        &mut motion::MovingBody,
+        &mut Brain,
+        &mut motion::TargetPoint,
+        &mut Target,
+        &mut obstacle::Route,
+    )>,
+    index: Res<spatial::SpatialIndex>,
+    nav: Res<obstacle::NavGrid>,
+    registry: Res<SpeciesRegistry>,
+    scenario: Res<Scenario>,
+    mut rng: ResMut<rng::SimRng>,
+    time: Res<Time<Fixed>>,
+) {
+    let world = &scenario.world;
+
+    for (
+        entity,
+        transform,
+        rotation,
+        unit,
+        genome,
+        senses,
+        hunger,
+        mut moving_body,
+        mut brain,
+        mut target_point,
+        mut target,
+        mut route,
+    ) in unit_query.iter_mut()
+    {
+        let pos = transform.translation.truncate();
+        let species = registry.get(unit.0);
+        let surroundings = Surroundings {
+            entity,
+            pos,
+            rotation: rotation.0,
+            species,
+            senses,
+            index: &index,
+            world,
+            nav: &nav,
+        };
+        let agent = steering::Agent {
+            velocity: moving_body.curr_velocity.truncate(),
+            max_speed: moving_body.max_speed,
+            max_acceleration: moving_body.max_acceleration,
+            radius: collision::body_radius(species, transform.scale.x),
+        };
+        target.0 = None;
+
+        let (point, acceleration) = match brain.as_mut() {
+            Brain::Seeker { heading } => match surroundings.food(&mut route) {
+                Some((food, food_body)) => {
+                    target.0 = Some(food);
+                    // Keep searching this way if the food gets out of sight.
+                    if agent.velocity != Vec2::ZERO {
+                        *heading = agent.velocity.to_angle();
+                    }
+                    (Some(food_body.offset), steering::pursue(&agent, &food_body))
+                }
+                None => {
+                    drift(heading, SEARCH_TURN_RATE, &mut rng, time.delta_secs());
+                    let point = WANDER_DISTANCE * Vec2::from_angle(*heading);
+                    (Some(point), steering::wander(&agent, *heading))
+                }
+            },
+            Brain::Wanderer { turn_rate, heading } => {
+                drift(heading, *turn_rate, &mut rng, time.delta_secs());
+                let point = WANDER_DISTANCE * Vec2::from_angle(*heading);
+                (Some(point), steering::wander(&agent, *heading))
+            }
+            Brain::Scripted {
+                waypoints,
+                arrival_radius,
+                next,
+            } => {
+                if world.offset(pos, waypoints[*next]).length() < *arrival_radius {
+                    *next = (*next + 1) % waypoints.len();
+                }
+                let offset = obstacle::route(
+                    world,
+                    &nav,
+                    &mut route,
+                    pos,
+                    world.offset(pos, waypoints[*next]),
+                );
+                (Some(offset), steering::seek(&agent, offset))
+            }
+            Brain::Neural { hidden } => {
+                let food = surroundings.food(&mut route);
+                target.0 = food.map(|(food, _)| food);
+                let senses = neural::Senses {
+                    food: food.map(|(_, food_body)| food_body.offset),
+                    predator: surroundings.predator().map(|predator| predator.offset),
+                    sensor_range: senses.vision_radius,
+                    hunger_ratio: hunger.curr_fullness / hunger.max_fullness,
+                    velocity: agent.velocity / moving_body.max_speed,
+                };
+                let steering = neural::evaluate(&genome.brain_weights, *hidden, &senses);
+
+                (
+                    Some(NEURAL_STEERING_DISTANCE * steering),
+                    steering::seek(&agent, steering),
+                )
+            }
+            Brain::Steering {
+                behaviours,
+                heading,
+            } => {
+                let time = time.delta_secs();
+                let food = surroundings.food(&mut route);
+                let predator = surroundings.predator();
+                let mut flock = None;
+                // Food behaviours search for food while there is none in sight,
+                // unless the brain already wanders.
+                let searches = !behaviours
+                    .iter()
+                    .any(|(_, behaviour)| matches!(behaviour, steering::Behaviour::Wander { .. }));
+                let mut search = None;
+
+                let mut weighted = Vec::with_capacity(behaviours.len());
+                for &(weight, behaviour) in behaviours.iter() {
+                    let acceleration = match behaviour {
+                        steering::Behaviour::SeekFood
+                        | steering::Behaviour::PursueFood
+                        | steering::Behaviour::ArriveFood { .. }
+                            if food.is_none() =>
+                        {
+                            if searches {
+                                *search.get_or_insert_with(|| {
+                                    drift(heading, SEARCH_TURN_RATE, &mut rng, time);
+                                    steering::wander(&agent, *heading)
+                                })
+                            } else {
+                                Vec2::ZERO
+                            }
+                        }
+                        steering::Behaviour::SeekFood => {
+                            food.map_or(Vec2::ZERO, |(_, f)| steering::seek(&agent, f.offset))
+                        }
+                        steering::Behaviour::PursueFood => {
+                            food.map_or(Vec2::ZERO, |(_, f)| steering::pursue(&agent, &f))
+                        }
+                        steering::Behaviour::ArriveFood { slowing_radius } => food
+                            .map_or(Vec2::ZERO, |(_, f)| {
+                                steering::arrive(&agent, f.offset, slowing_radius)
+                            }),
+                        steering::Behaviour::FleePredators => {
+                            predator.map_or(Vec2::ZERO, |p| steering::flee(&agent, p.offset))
+                        }
+                        steering::Behaviour::EvadePredators => {
+                            predator.map_or(Vec2::ZERO, |p| steering::evade(&agent, &p))
+                        }
+                        steering::Behaviour::Wander { turn_rate } => {
+                            drift(heading, turn_rate, &mut rng, time);
+                            steering::wander(&agent, *heading)
+                        }
+                        steering::Behaviour::AvoidObstacles => {
+                            // Food is not in the way, it is where the unit
+                            // wants to go.
+                            let obstacles = surroundings.units_within(
+                                AVOIDANCE_DISTANCE + agent.radius + index.units.max_radius(),
+                                |other| !species.diet.prey.contains(&other.species),
+                            );
+                            steering::avoid_obstacles(&agent, obstacles, AVOIDANCE_DISTANCE)
+                        }
+                        steering::Behaviour::Separation => steering::separation(
+                            &agent,
+                            flock.get_or_insert_with(|| flockmates(&surroundings, unit)),
+                        ),
+                        steering::Behaviour::Alignment => steering::alignment(
+                            &agent,
+                            flock.get_or_insert_with(|| flockmates(&surroundings, unit)),
+                        ),
+                        steering::Behaviour::Cohesion => steering::cohesion(
+                            &agent,
+                            flock.get_or_insert_with(|| flockmates(&surroundings, unit)),
+                        ),
+                    };
+                    weighted.push((weight, acceleration));
+                }
+
+                if behaviours.iter().any(|(_, behaviour)| behaviour.is_food()) {
+                    target.0 = food.map(|(food, _)| food);
+                }
+                let acceleration = steering::blend(&agent, weighted);
+                (
+                    acceleration
+                        .try_normalize()
+                        .map(|direction| STEERING_DISTANCE * direction),
+                    acceleration,
+                )
+            }
+        };
+
+        // Whatever the brain decided, steer clear of obstacles ahead.
+        let ahead = agent
+            .velocity
+            .try_normalize()
+            .or_else(|| acceleration.try_normalize());
+        let hit = ahead.and_then(|ahead| {
+            world
+                .look_ahead(pos, ahead, agent.radius, AVOIDANCE_DISTANCE)
+                .map(|hit| (ahead, hit))
+        });
+        let acceleration = match hit {
+            Some((ahead, hit)) => (acceleration
+                + steering::avoid_surface(
+                    &agent,
+                    ahead,
+                    hit.distance,
+                    hit.normal,
+                    AVOIDANCE_DISTANCE,
+                ))
+            .clamp_length_max(agent.max_acceleration),
+            None => acceleration,
+        };
+
+        target_point.0 = point.map(|point| point.extend(0.0));
+        moving_body.curr_acceleration = acceleration.extend(0.0);
+    }
+}
+
+/// Let `heading` drift randomly by `turn_rate` radians per square root second
+/// over `time` seconds.
+fn drift(heading: &mut f32, turn_rate: f32, rng: &mut rng::SimRng, time: f32) {
+    let drift: f32 = StandardNormal.sample(&mut rng.0);
+    *heading += drift * turn_rate * time.sqrt();
+}
+
+/// Units of the same species that a unit flocks with.
+fn flockmates(surroundings: &Surroundings, unit: &unit::UnitType) -> Vec<steering::Neighbour> {
+    surroundings.units_within(FLOCK_RADIUS, |other| other.species == unit.0)
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand_distr::{Distribution, StandardNormal};
+
+/// Number of sensor readings fed to the network.
+pub const INPUTS: usize = 9;
+/// Number of values produced by the network: a steering direction.
+pub const OUTPUTS: usize = 2;
+
+/// What a unit with a neural network brain perceives. Directions are relative
+/// to the unit and `None` when nothing is within sensor range.
+pub struct Senses {
+    pub food: Option<Vec2>,
+    pub predator: Option<Vec2>,
+    pub sensor_range: f32,
+    /// Current fullness divided by maximum fullness.
+    pub hunger_ratio: f32,
+    /// Current velocity divided by maximum speed.
+    pub velocity: Vec2,
+}
+
+impl Senses {
+    fn inputs(&self) -> [f32; INPUTS] {
+        let (food_dir, food_dist) = self.direction_and_distance(self.food);
+        let (predator_dir, predator_dist) = self.direction_and_distance(self.predator);
+
+        [
+            food_dir.x,
+            food_dir.y,
+            food_dist,
+            predator_dir.x,
+            predator_dir.y,
+            predator_dist,
+            self.hunger_ratio,
+            self.velocity.x,
+            self.velocity.y,
+        ]
+    }
+
+    /// Unit direction and distance relative to the sensor range. Things out
+    /// of range are reported as being in no direction at the maximum distance.
+    fn direction_and_distance(&self, offset: Option<Vec2>) -> (Vec2, f32) {
+        match offset {
+            Some(offset) => (
+                offset.normalize_or_zero(),
+                (offset.length() / self.sensor_range).min(1.0),
+            ),
+            None => (Vec2::ZERO, 1.0),
+        }
+    }
+}
+
+/// Number of weights, including biases, in a network with one hidden layer of
+/// `hidden` neurons.
+pub fn weight_count(hidden: usize) -> usize {
+    (INPUTS + 1) * hidden + (hidden + 1) * OUTPUTS
+}
+
+/// Weights for a network that has not inherited any, drawn from a standard
+/// normal distribution scaled by the fan-in of each layer.
+pub fn random_weights(hidden: usize, rng: &mut impl Rng) -> Vec<f32> {
+    let mut weights = Vec::with_capacity(weight_count(hidden));
+    for (count, fan_in) in [
+        ((INPUTS + 1) * hidden, INPUTS + 1),
+        ((hidden + 1) * OUTPUTS, hidden + 1),
+    ] {
+        let scale = 1.0 / (fan_in as f32).sqrt();
+        for _ in 0..count {
+            let weight: f32 = StandardNormal.sample(rng);
+            weights.push(weight * scale);
+        }
+    }
+
+    weights
+}
+
+/// Run the network and return the steering direction it outputs, with each
+/// component in `-1.0..=1.0`. Weights that do not make up a network of
+/// `hidden` neurons steer nowhere.
+pub fn evaluate(weights: &[f32], hidden: usize, senses: &Senses) -> Vec2 {
+    if weights.len() != weight_count(hidden) {
+        return Vec2::ZERO;
+    }
+
+    let inputs = senses.inputs();
+    let (hidden_weights, output_weights) = weights.split_at((INPUTS + 1) * hidden);
+
+    let activations: Vec<f32> = hidden_weights
+        .chunks_exact(INPUTS + 1)
+        .map(|neuron| layer_output(neuron, &inputs))
+        .collect();
+
+    let mut outputs = output_weights
+        .chunks_exact(hidden + 1)
+        .map(|neuron| layer_output(neuron, &activations));
+
+    Vec2::new(
+        outputs.next().unwrap_or_default(),
+        outputs.next().unwrap_or_default(),
+    )
+}
+
+/// Output of a single neuron whose last weight is its bias.
+fn layer_output(weights: &[f32], inputs: &[f32]) -> f32 {
+    let (bias, weights) = weights.split_last().expect("neuron without weights");
+    let sum: f32 = weights.iter().zip(inputs).map(|(w, x)| w * x).sum();
+    (sum + bias).tanh()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const SENSES: Senses = Senses {
+        food: Some(Vec2::new(30.0, -40.0)),
+        predator: None,
+        sensor_range: 100.0,
+        hunger_ratio: 0.5,
+        velocity: Vec2::new(0.2, 0.1),
+    };
+
+    #[test]
+    fn evaluate_steers_within_range() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for hidden in [1, 4, 16] {
+            let weights = random_weights(hidden, &mut rng);
+            let steering = evaluate(&weights, hidden, &SENSES);
+
+            assert!(steering != Vec2::ZERO);
+            assert!(steering.abs().max_element() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn evaluate_ignores_weights_of_another_size() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let weights = random_weights(4, &mut rng);
+
+        for len in [0, 1, weights.len() - 1] {
+            assert_eq!(evaluate(&weights[..len], 4, &SENSES), Vec2::ZERO);
+        }
+        assert_eq!(evaluate(&weights, 5, &SENSES), Vec2::ZERO);
+        assert_eq!(evaluate(&weights, 3, &SENSES), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,90 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::simulation::*;
+
+/// Pixels of scrolling that zoom as much as one line.
+const PIXELS_PER_LINE: f32 = 100.0;
+
+/// Whether the camera is locked onto the selected unit. Toggled with F.
+#[derive(Resource, Default)]
+pub struct CameraFollow(pub bool);
+
+/// Zoom around the centre of the screen with the mouse wheel.
+pub fn zoom_camera(
+    mut wheel: EventReader<MouseWheel>,
+    mut projection: Single<&mut Projection, With<Camera2d>>,
+) {
+    let lines: f32 = wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        })
+        .sum();
+    if lines == 0.0 {
+        return;
+    }
+
+    if let Projection::Orthographic(orthographic) = projection.as_mut() {
+        // Scrolling up zooms in, which shrinks the visible area.
+        orthographic.scale = (orthographic.scale * CAMERA_ZOOM_STEP.powf(-lines))
+            .clamp(CAMERA_MIN_SCALE, CAMERA_MAX_SCALE);
+    }
+}
+
+/// Show the scenario's whole world when Z is pressed, as far as the zoom
+/// limits allow.
+pub fn fit_playable_area(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    camera: Single<(&mut Transform, &mut Projection), With<Camera2d>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    scenario: Res<Scenario>,
+    mut follow: ResMut<CameraFollow>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyZ) {
+        return;
+    }
+
+    let (mut transform, mut projection) = camera.into_inner();
+    let bounds = scenario.world.bounds;
+    // A minimised window has no size.
+    let fit = bounds.size() / window.size().max(Vec2::ONE);
+
+    follow.0 = false;
+    transform.translation = ((bounds.min() + bounds.max()) / 2.0).extend(transform.translation.z);
+    if let Projection::Orthographic(orthographic) = projection.as_mut() {
+        orthographic.scale =
+            (fit.max_element() * CAMERA_FIT_MARGIN).clamp(CAMERA_MIN_SCALE, CAMERA_MAX_SCALE);
+    }
+}
+
+pub fn toggle_follow(keyboard_input: Res<ButtonInput<KeyCode>>, mut follow: ResMut<CameraFollow>) {
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        follow.0 = !follow.0;
+    }
+}
+
+/// Keep the selected unit in the centre of the screen while following it.
+/// Stops following once nothing is selected.
+pub fn follow_selection(
+    mut follow: ResMut<CameraFollow>,
+    selection: Res<inspect::Selection>,
+    mut camera: Single<&mut Transform, With<Camera2d>>,
+    unit_query: Query<&Transform, (With<unit::UnitType>, Without<Camera2d>)>,
+) {
+    if !follow.0 {
+        return;
+    }
+
+    match selection.0.and_then(|entity| unit_query.get(entity).ok()) {
+        Some(unit) => {
+            camera.translation.x = unit.translation.x;
+            camera.translation.y = unit.translation.y;
+        }
+        None => follow.0 = false,
+    }
+}
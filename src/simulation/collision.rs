@@ -0,0 +1,215 @@
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+
+use crate::simulation::*;
+
+/// A unit's body as seen by the collision response: a disc whose mass grows
+/// with its area.
+struct Body {
+    entity: Entity,
+    position: Vec2,
+    velocity: Vec2,
+    radius: f32,
+    inverse_mass: f32,
+}
+
+/// Radius of the disc that a unit's body collides as.
+pub fn body_radius(species: &Species, body_scale: f32) -> f32 {
+    species.body.width.max(species.body.height) * body_scale
+}
+
+/// Separate two overlapping bodies. `normal` points from `b` to `a` and
+/// `overlap` is how far their discs overlap along it.
+///
+/// Both bodies get equal and opposite impulses, so their total momentum is
+/// unchanged. The impulse removes the velocity at which they approach each
+/// other, and gives back `restitution` of it in the opposite direction.
+/// `correction` of the overlap is removed by moving the bodies apart in
+/// proportion to their inverse masses, which leaves their centre of mass in
+/// place.
+fn resolve_pair(
+    a: &mut Body,
+    b: &mut Body,
+    normal: Vec2,
+    overlap: f32,
+    config: &scenario::CollisionConfig,
+) {
+    let inverse_mass = a.inverse_mass + b.inverse_mass;
+
+    let approach = (a.velocity - b.velocity).dot(normal);
+    if approach < 0.0 {
+        let impulse = -(1.0 + config.restitution) * approach / inverse_mass;
+        a.velocity += impulse * a.inverse_mass * normal;
+        b.velocity -= impulse * b.inverse_mass * normal;
+    }
+
+    let correction = config.correction * overlap / inverse_mass;
+    a.position += correction * a.inverse_mass * normal;
+    b.position -= correction * b.inverse_mass * normal;
+}
+
+/// Push overlapping units apart. Every pair is resolved once, in query order,
+/// and later pairs see the result of earlier ones.
+pub fn resolve_collisions(
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &mut motion::MovingBody,
+        &unit::UnitType,
+    )>,
+    index: Res<spatial::SpatialIndex>,
+    scenario: Res<Scenario>,
+    registry: Res<SpeciesRegistry>,
+) {
+    let world = &scenario.world;
+    let config = &world.collisions;
+
+    let mut bodies: Vec<Body> = query
+        .iter()
+        .map(|(entity, transform, moving_body, unit)| {
+            let radius = body_radius(registry.get(unit.0), transform.scale.x);
+            Body {
+                entity,
+                position: transform.translation.truncate(),
+                velocity: moving_body.curr_velocity.truncate(),
+                radius,
+                inverse_mass: 1.0 / (radius * radius),
+            }
+        })
+        .collect();
+    let indices: EntityHashMap<usize> = bodies
+        .iter()
+        .enumerate()
+        .map(|(i, body)| (body.entity, i))
+        .collect();
+
+    for i in 0..bodies.len() {
+        let (position, radius) = (bodies[i].position, bodies[i].radius);
+
+        for shift in world.shifts(position, radius + index.units.max_radius()) {
+            for other in index.units.overlapping(position + shift, radius) {
+                let Some(&j) = indices.get(&other.entity) else {
+                    continue;
+                };
+                // Each pair once.
+                if j <= i {
+                    continue;
+                }
+
+                let [a, b] = bodies.get_disjoint_mut([i, j]).expect("distinct indices");
+                let offset = a.position - (b.position - shift);
+                let overlap = a.radius + b.radius - offset.length();
+                if overlap > 0.0 {
+                    // Bodies exactly on top of each other are pushed apart
+                    // sideways.
+                    let normal = offset.try_normalize().unwrap_or(Vec2::X);
+                    resolve_pair(a, b, normal, overlap, config);
+                }
+            }
+        }
+    }
+
+    for (body, (_, mut transform, mut moving_body, _)) in bodies.iter().zip(query.iter_mut()) {
+        transform.translation.x = body.position.x;
+        transform.translation.y = body.position.y;
+        moving_body.curr_velocity.x = body.velocity.x;
+        moving_body.curr_velocity.y = body.velocity.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: scenario::CollisionConfig = scenario::CollisionConfig {
+        restitution: 0.5,
+        correction: 0.8,
+    };
+
+    fn body(position: Vec2, velocity: Vec2, radius: f32) -> Body {
+        Body {
+            entity: Entity::PLACEHOLDER,
+            position,
+            velocity,
+            radius,
+            inverse_mass: 1.0 / (radius * radius),
+        }
+    }
+
+    fn mass(body: &Body) -> f32 {
+        1.0 / body.inverse_mass
+    }
+
+    fn momentum(a: &Body, b: &Body) -> Vec2 {
+        mass(a) * a.velocity + mass(b) * b.velocity
+    }
+
+    fn centre_of_mass(a: &Body, b: &Body) -> Vec2 {
+        (mass(a) * a.position + mass(b) * b.position) / (mass(a) + mass(b))
+    }
+
+    /// A small body at the origin moving right into a large one that moves
+    /// left, overlapping it by 4.
+    fn approaching() -> (Body, Body) {
+        (
+            body(Vec2::ZERO, Vec2::new(30.0, 5.0), 10.0),
+            body(Vec2::new(26.0, 0.0), Vec2::new(-10.0, 2.0), 20.0),
+        )
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.abs_diff_eq(b, 1e-3 * b.length().max(1.0)), "{a} != {b}");
+    }
+
+    #[test]
+    fn impulses_are_equal_and_opposite() {
+        let (mut a, mut b) = approaching();
+        let (a_before, b_before) = (a.velocity, b.velocity);
+
+        resolve_pair(&mut a, &mut b, Vec2::NEG_X, 4.0, &CONFIG);
+
+        let a_impulse = mass(&a) * (a.velocity - a_before);
+        let b_impulse = mass(&b) * (b.velocity - b_before);
+        assert!(a_impulse.length() > 0.0);
+        assert_close(a_impulse, -b_impulse);
+        // Along the normal only.
+        assert_eq!(a_impulse.y, 0.0);
+    }
+
+    #[test]
+    fn momentum_is_conserved_with_different_radii() {
+        let (mut a, mut b) = approaching();
+        let before = momentum(&a, &b);
+
+        resolve_pair(&mut a, &mut b, Vec2::NEG_X, 4.0, &CONFIG);
+
+        assert_close(momentum(&a, &b), before);
+        // They no longer approach each other, and separate with the
+        // restitution's share of the speed they approached at.
+        let separation = (a.velocity - b.velocity).dot(Vec2::NEG_X);
+        assert!((separation - CONFIG.restitution * 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn correction_keeps_centre_of_mass() {
+        let (mut a, mut b) = approaching();
+        let before = centre_of_mass(&a, &b);
+
+        resolve_pair(&mut a, &mut b, Vec2::NEG_X, 4.0, &CONFIG);
+
+        assert_close(centre_of_mass(&a, &b), before);
+        let distance = a.position.distance(b.position);
+        assert!((distance - (26.0 + CONFIG.correction * 4.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn separating_bodies_get_no_impulse() {
+        let mut a = body(Vec2::ZERO, Vec2::new(-30.0, 5.0), 10.0);
+        let mut b = body(Vec2::new(26.0, 0.0), Vec2::new(10.0, 2.0), 20.0);
+        let (a_before, b_before) = (a.velocity, b.velocity);
+
+        resolve_pair(&mut a, &mut b, Vec2::NEG_X, 4.0, &CONFIG);
+
+        assert_eq!(a.velocity, a_before);
+        assert_eq!(b.velocity, b_before);
+    }
+}
@@ -1,24 +1,92 @@
 pub const NUM_ZYRTHIDS: usize = 3;
 pub const NUM_FERNWORMS: usize = 100;
-pub const MAX_BERRIES: u64 = 20;
 
 pub const BERRY_FULLNESS_GAIN: f32 = 40.0;
-pub const FERNWORM_FULLNESS_GAIN: f32 = 80.0;
+
+/// Biomass that a corpse loses to decay per second.
+pub const CORPSE_DECAY_RATE: f32 = 4.0;
+/// Nutrients from decayed corpses that it takes to grow a berry.
+pub const BERRY_NUTRIENT_COST: f32 = BERRY_FULLNESS_GAIN;
+/// Maximum distance from a corpse that berries grow from it at.
+pub const CORPSE_BERRY_RADIUS: f32 = 60.0;
+
+/// Berries that a bush carries when full, unless the scenario says otherwise.
+pub const BUSH_CAPACITY: u32 = 3;
+/// Berries per second that a bush grows back, unless the scenario says
+/// otherwise.
+pub const BUSH_REGROWTH_RATE: f32 = 0.2;
+/// Maximum distance from a bush that its berries grow at.
+pub const BUSH_RADIUS: f32 = 30.0;
+/// Bushes in each patch of the default scenario.
+pub const BUSHES_PER_PATCH: usize = 5;
+/// Maximum distance from the center of a patch in the default scenario that
+/// its bushes are placed at.
+pub const PATCH_RADIUS: f32 = 120.0;
 
 pub const BERRY_RENDER_HEIGHT: f32 = 16.0;
 pub const BERRY_RENDER_WIDTH: f32 = 16.0;
+pub const BUSH_RENDER_SIZE: f32 = 64.0;
+/// Z coordinate of the terrain map, above the background.
+pub const TERRAIN_DEPTH: f32 = 0.1;
+/// Z coordinate of obstacles, beneath berries and units.
+pub const OBSTACLE_DEPTH: f32 = 0.8;
+/// Z coordinate of bushes, beneath their berries.
+pub const BUSH_DEPTH: f32 = 0.5;
+
+/// Share of the approach speed that colliding bodies bounce apart with, unless
+/// the scenario says otherwise.
+pub const COLLISION_RESTITUTION: f32 = 0.2;
+/// Share of the overlap between two bodies that is removed every tick, unless
+/// the scenario says otherwise.
+pub const COLLISION_CORRECTION: f32 = 0.5;
+
+/// Side length of the cells of the grid that routes around obstacles are
+/// planned on.
+pub const NAV_CELL_SIZE: f32 = 25.0;
+/// Distance from obstacles within which routes around them avoid leading.
+pub const NAV_CLEARANCE: f32 = 60.0;
+/// Cost of moving through a cell within `NAV_CLEARANCE` of an obstacle,
+/// relative to a cell in the open.
+pub const NAV_NEAR_OBSTACLE_COST: f32 = 5.0;
+/// Attempts at finding a random spawn position outside all obstacles.
+pub const SPAWN_ATTEMPTS: usize = 100;
+
+/// Share of the speed towards a wall that a unit keeps when bouncing off it,
+/// unless the scenario says otherwise.
+pub const BOUNDARY_RESTITUTION: f32 = 0.5;
+
+/// Side length of the cells of the spatial index. A collision query around a
+/// unit only has to look at the neighbouring cells.
+pub const SPATIAL_CELL_SIZE: f32 = 100.0;
+
+/// Ticks between two samples of the population statistics.
+pub const STATS_INTERVAL: u64 = 16;
+/// File that statistics are written to on request when no file was given on
+/// the command line.
+pub const DEFAULT_STATS_FILE: &str = "stats.csv";
+
+/// Ticks between two frames of a replay recording.
+pub const REPLAY_INTERVAL: u64 = 4;
+
+/// Simulation speeds selected with the number keys, starting at 1.
+pub const SPEED_STEPS: [f32; 4] = [1.0, 2.0, 4.0, 16.0];
+/// Frame time that the maximum simulation speed aims for.
+pub const MAX_SPEED_FRAME_SECS: f32 = 1.0 / 30.0;
+
+/// Ticks between two samples of the population chart in the HUD.
+pub const HUD_SAMPLE_INTERVAL: u64 = 32;
+/// Number of samples shown in the population chart.
+pub const HUD_HISTORY_LEN: usize = 240;
+pub const HUD_CHART_WIDTH: u32 = 240;
+pub const HUD_CHART_HEIGHT: u32 = 100;
 
-pub const FERNWORM_SPRITE_HEIGHT: u32 = 16;
-pub const FERNWORM_SPRITE_WIDTH: u32 = 7;
-pub const FERNWORM_SCALE_FACTOR: f32 = 4.0;
-pub const FERNWORM_RENDER_HEIGHT: f32 = (FERNWORM_SPRITE_HEIGHT as f32) * FERNWORM_SCALE_FACTOR;
-pub const FERNWORM_RENDER_WIDTH: f32 = (FERNWORM_SPRITE_WIDTH as f32) * FERNWORM_SCALE_FACTOR;
-
-pub const ZYRTHID_SPRITE_HEIGHT: u32 = 25;
-pub const ZYRTHID_SPRITE_WIDTH: u32 = 11;
-pub const ZYRTHID_SCALE_FACTOR: f32 = 4.0;
-pub const ZYRTHID_RENDER_HEIGHT: f32 = (ZYRTHID_SPRITE_HEIGHT as f32) * ZYRTHID_SCALE_FACTOR;
-pub const ZYRTHID_RENDER_WIDTH: f32 = (ZYRTHID_SPRITE_WIDTH as f32) * ZYRTHID_SCALE_FACTOR;
+/// Factor the camera zooms by per line scrolled.
+pub const CAMERA_ZOOM_STEP: f32 = 1.1;
+/// Limits of the camera's projection scale. Smaller is closer.
+pub const CAMERA_MIN_SCALE: f32 = 0.25;
+pub const CAMERA_MAX_SCALE: f32 = 4.0;
+/// Room left around the playable area when fitting the camera to it.
+pub const CAMERA_FIT_MARGIN: f32 = 1.05;
 
 pub const SCREEN_WIDTH: f32 = 1920.0;
 pub const SCREEN_HEIGHT: f32 = 1080.0;
@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+
+use crate::simulation::*;
+
+/// Let corpses decay. Their decayed biomass turns into nutrients, and every
+/// `BERRY_NUTRIENT_COST` of nutrients grows a berry near the corpse. Corpses
+/// are despawned once nothing is left of them.
+pub fn decay_corpses(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Transform, &mut unit::Corpse)>,
+    mut game_data: ResMut<SimData>,
+    mut rng: ResMut<rng::SimRng>,
+    scenario: Res<Scenario>,
+    time: Res<Time<Fixed>>,
+) {
+    for (entity, transform, mut corpse) in query.iter_mut() {
+        let decayed = corpse.biomass.min(CORPSE_DECAY_RATE * time.delta_secs());
+        corpse.biomass -= decayed;
+        corpse.nutrients += decayed;
+
+        while corpse.nutrients >= BERRY_NUTRIENT_COST {
+            corpse.nutrients -= BERRY_NUTRIENT_COST;
+
+            let position = berry::position_near(
+                transform.translation.truncate(),
+                CORPSE_BERRY_RADIUS,
+                &scenario.world,
+                &mut rng,
+            );
+            berry::spawn_berry(
+                &mut commands,
+                position,
+                berry::Berry {
+                    fullness: BERRY_FULLNESS_GAIN,
+                    bush: None,
+                },
+            );
+            game_data.num_berries += 1;
+        }
+
+        // Nutrients too few for another berry are lost with the corpse.
+        if corpse.biomass <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
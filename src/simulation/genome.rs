@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+
+/// Smallest factor a single mutation can scale a trait by. Keeps traits
+/// positive no matter how large the mutation strength is.
+const MIN_MUTATION_FACTOR: f32 = 0.01;
+
+/// Heritable traits of a unit. Offspring inherit the genome of their parent,
+/// possibly mutated, and the unit's other components are derived from it when
+/// it is spawned.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Genome {
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    pub max_fullness: f32,
+    pub drain_per_unit_traveled: f32,
+    /// Scale of the unit's body relative to its species' size.
+    pub body_scale: f32,
+    /// Distance within which the unit notices food.
+    pub sensor_range: f32,
+    /// Weights of the unit's neural network brain. Empty for other brains.
+    pub brain_weights: Vec<f32>,
+}
+
+/// How genomes change from parent to offspring.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Mutation {
+    /// Probability that each trait is mutated.
+    pub probability: f32,
+    /// Standard deviation of the Gaussian noise, relative to the trait's value.
+    /// Neural network weights get noise with this absolute standard deviation
+    /// instead, since they have no natural scale.
+    pub strength: f32,
+}
+
+impl Genome {
+    /// Copy of the genome where each trait has been scaled by `1 + N(0,
+    /// strength)` with the configured probability.
+    pub fn mutated(&self, mutation: &Mutation, rng: &mut impl Rng) -> Genome {
+        // Validated when the species or scenario is loaded.
+        let noise = Normal::new(0.0, mutation.strength).expect("invalid mutation strength");
+        let mut mutate = |value: f32| {
+            if rng.gen::<f32>() < mutation.probability {
+                value * (1.0 + noise.sample(rng)).max(MIN_MUTATION_FACTOR)
+            } else {
+                value
+            }
+        };
+
+        let max_speed = mutate(self.max_speed);
+        let max_acceleration = mutate(self.max_acceleration);
+        let max_fullness = mutate(self.max_fullness);
+        let drain_per_unit_traveled = mutate(self.drain_per_unit_traveled);
+        let body_scale = mutate(self.body_scale);
+        let sensor_range = mutate(self.sensor_range);
+
+        let brain_weights = self
+            .brain_weights
+            .iter()
+            .map(|weight| {
+                if rng.gen::<f32>() < mutation.probability {
+                    weight + noise.sample(rng)
+                } else {
+                    *weight
+                }
+            })
+            .collect();
+
+        Genome {
+            max_speed,
+            max_acceleration,
+            max_fullness,
+            drain_per_unit_traveled,
+            body_scale,
+            sensor_range,
+            brain_weights,
+        }
+    }
+}
+
+impl Mutation {
+    /// Returns the name of the first invalid field, if any.
+    pub fn invalid_field(&self) -> Option<&'static str> {
+        if !(0.0..=1.0).contains(&self.probability) {
+            Some("probability")
+        } else if !(self.strength.is_finite() && self.strength >= 0.0) {
+            Some("strength")
+        } else {
+            None
+        }
+    }
+}
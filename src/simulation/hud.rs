@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::simulation::{presentation::clock, *};
+
+/// Line colours of the species in the chart, by species id. Repeats if there
+/// are more species than colours.
+const SPECIES_COLORS: [Color; 6] = [
+    Color::srgb(0.95, 0.75, 0.25),
+    Color::srgb(0.9, 0.3, 0.3),
+    Color::srgb(0.4, 0.6, 1.0),
+    Color::srgb(0.8, 0.45, 0.95),
+    Color::srgb(0.35, 0.9, 0.9),
+    Color::srgb(1.0, 1.0, 1.0),
+];
+const BERRY_COLOR: Color = Color::srgb(0.3, 0.85, 0.3);
+const CHART_BACKGROUND: [u8; 4] = [0, 0, 0, 140];
+
+/// Root of the overlay. Hidden and shown with H.
+#[derive(Component)]
+pub struct Hud;
+
+#[derive(Component)]
+pub struct HudText;
+
+/// Population counts of the last `HUD_HISTORY_LEN` samples, oldest first,
+/// drawn into `image`.
+#[derive(Resource)]
+pub struct PopulationHistory {
+    samples: VecDeque<HistorySample>,
+    image: Handle<Image>,
+}
+
+struct HistorySample {
+    species: Vec<usize>,
+    berries: usize,
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    registry: Res<SpeciesRegistry>,
+) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: HUD_CHART_WIDTH,
+            height: HUD_CHART_HEIGHT,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &CHART_BACKGROUND,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    ));
+
+    commands.insert_resource(PopulationHistory {
+        samples: VecDeque::with_capacity(HUD_HISTORY_LEN),
+        image: image.clone(),
+    });
+
+    let font = TextFont {
+        font_size: 16.0,
+        ..default()
+    };
+
+    // One span for the time and one per species and for the berries, each in
+    // the colour of its line in the chart.
+    let mut text = commands.spawn((HudText, Text::new(""), font.clone()));
+    for (id, _) in registry.iter() {
+        text.with_child((
+            TextSpan::new(""),
+            font.clone(),
+            TextColor(species_color(id)),
+        ));
+    }
+    text.with_child((TextSpan::new(""), font, TextColor(BERRY_COLOR)));
+    let text = text.id();
+
+    commands
+        .spawn((
+            SimulationComponent,
+            Hud,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.),
+                top: Val::Px(10.),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.),
+                padding: UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.4)),
+        ))
+        .add_child(text)
+        .with_child((
+            ImageNode::new(image),
+            Node {
+                width: Val::Px(HUD_CHART_WIDTH as f32),
+                height: Val::Px(HUD_CHART_HEIGHT as f32),
+                ..default()
+            },
+        ));
+}
+
+fn species_color(id: species::SpeciesId) -> Color {
+    SPECIES_COLORS[id.0 % SPECIES_COLORS.len()]
+}
+
+/// Record the population every `HUD_SAMPLE_INTERVAL` ticks and redraw the
+/// chart. Runs in the fixed schedule, so the chart covers the same span of
+/// simulated time at any speed.
+pub fn sample_history(
+    mut history: ResMut<PopulationHistory>,
+    mut images: ResMut<Assets<Image>>,
+    game_data: Res<SimData>,
+    unit_query: Query<&unit::UnitType>,
+    berry_query: Query<(), With<berry::Berry>>,
+    registry: Res<SpeciesRegistry>,
+) {
+    if !game_data.tick.is_multiple_of(HUD_SAMPLE_INTERVAL) {
+        return;
+    }
+
+    let mut species = vec![0; registry.iter().count()];
+    for unit in unit_query.iter() {
+        species[unit.0 .0] += 1;
+    }
+
+    if history.samples.len() == HUD_HISTORY_LEN {
+        history.samples.pop_front();
+    }
+    history.samples.push_back(HistorySample {
+        species,
+        berries: berry_query.iter().count(),
+    });
+
+    if let Some(image) = images.get_mut(&history.image) {
+        draw_chart(image, &history.samples);
+    }
+}
+
+/// Draw one line per species and one for the berries, scaled so that the
+/// largest count reaches the top. The newest sample is at the right edge.
+fn draw_chart(image: &mut Image, samples: &VecDeque<HistorySample>) {
+    let Some(data) = image.data.as_mut() else {
+        return;
+    };
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&CHART_BACKGROUND);
+    }
+
+    let max = samples
+        .iter()
+        .flat_map(|sample| sample.species.iter().copied().chain([sample.berries]))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let x_step = (HUD_CHART_WIDTH - 1) as f32 / (HUD_HISTORY_LEN - 1) as f32;
+    let x_offset = (HUD_HISTORY_LEN - samples.len()) as f32 * x_step;
+    let point = |i: usize, count: usize| {
+        let x = x_offset + i as f32 * x_step;
+        let y = (HUD_CHART_HEIGHT - 1) as f32 * (1.0 - count as f32 / max as f32);
+        Vec2::new(x, y)
+    };
+
+    // The berries are the line after the last species.
+    let species_count = samples.front().map_or(0, |sample| sample.species.len());
+    let count = |sample: &HistorySample, line: usize| {
+        sample.species.get(line).copied().unwrap_or(sample.berries)
+    };
+
+    for line in 0..=species_count {
+        let color = if line < species_count {
+            species_color(species::SpeciesId(line))
+        } else {
+            BERRY_COLOR
+        };
+
+        for (i, (a, b)) in samples.iter().zip(samples.iter().skip(1)).enumerate() {
+            draw_line(
+                image,
+                point(i, count(a, line)),
+                point(i + 1, count(b, line)),
+                color,
+            );
+        }
+    }
+}
+
+fn draw_line(image: &mut Image, from: Vec2, to: Vec2, color: Color) {
+    let steps = (to - from).abs().max_element().ceil().max(1.0) as u32;
+    for step in 0..=steps {
+        let p = from.lerp(to, step as f32 / steps as f32).round();
+        let _ = image.set_color_at(p.x as u32, p.y as u32, color);
+    }
+}
+
+pub fn update_hud_text(
+    text: Single<Entity, With<HudText>>,
+    mut writer: TextUiWriter,
+    game_data: Res<SimData>,
+    fixed_time: Res<Time<Fixed>>,
+    unit_query: Query<&unit::UnitType>,
+    berry_query: Query<(), With<berry::Berry>>,
+    registry: Res<SpeciesRegistry>,
+) {
+    let mut counts = vec![0; registry.iter().count()];
+    for unit in unit_query.iter() {
+        counts[unit.0 .0] += 1;
+    }
+
+    let secs = game_data.tick as f64 * fixed_time.timestep().as_secs_f64();
+    *writer.text(*text, 0) = format!("Time {}", clock(secs));
+    for (span, ((_, species), count)) in registry.iter().zip(&counts).enumerate() {
+        *writer.text(*text, span + 1) = format!("\n{}: {count}", species.name);
+    }
+    *writer.text(*text, counts.len() + 1) = format!("\nBerries: {}", berry_query.iter().count());
+}
+
+pub fn toggle_hud(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut query: Query<&mut Visibility, With<Hud>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyH) {
+        for mut visibility in query.iter_mut() {
+            visibility.toggle_visible_hidden();
+        }
+    }
+}
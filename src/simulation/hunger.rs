@@ -1,8 +1,9 @@
-use bevy::prelude::*;
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+use serde::{Deserialize, Serialize};
 
 use crate::simulation::*;
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Hunger {
     pub curr_fullness: f32,
     pub max_fullness: f32,
@@ -10,15 +11,28 @@ pub struct Hunger {
     pub last_sampled_pos: Vec3,
 }
 
-pub fn hunger_drain(mut query: Query<(&Transform, &mut Hunger)>) {
+pub fn hunger_drain(
+    mut query: Query<(&Transform, &mut Hunger)>,
+    scenario: Res<Scenario>,
+    terrain: Res<terrain::Terrain>,
+) {
     for (transform, mut hunger) in query.iter_mut() {
-        let dist = hunger.last_sampled_pos.distance(transform.translation);
+        // Crossing the edge of a wrapping world is not a long way to travel.
+        let dist = scenario
+            .world
+            .offset(
+                hunger.last_sampled_pos.truncate(),
+                transform.translation.truncate(),
+            )
+            .length();
         hunger.last_sampled_pos = transform.translation;
 
-        hunger.curr_fullness -= dist * hunger.drain_per_unit_traveled;
+        let drain = terrain.effects_at(transform.translation.truncate()).drain;
+        hunger.curr_fullness -= dist * hunger.drain_per_unit_traveled * drain;
     }
 }
 
+/// Kill units that have run out of fullness, unless they have just been eaten.
 pub fn kill_starved_units(
     query: Query<(
         Entity,
@@ -27,16 +41,26 @@ pub fn kill_starved_units(
         &Transform,
         &motion::Rotation,
     )>,
-    mut events: EventWriter<unit::DeathEvent>,
+    mut events: ResMut<Events<unit::DeathEvent>>,
+    registry: Res<SpeciesRegistry>,
 ) {
+    // A unit dies only once, so it leaves one corpse and is counted once.
+    let dead: EntityHashSet = events
+        .iter_current_update_events()
+        .map(|event| event.entity)
+        .collect();
+
     for (entity, unit, hunger, transform, rotation) in query.iter() {
-        if hunger.curr_fullness <= 0.0 {
-            events.write(unit::DeathEvent {
-                entity: entity,
+        if hunger.curr_fullness <= 0.0 && !dead.contains(&entity) {
+            events.send(unit::DeathEvent {
+                entity,
+                unit: *unit,
+                cause: unit::DeathCause::Starvation,
                 corpse: Some(unit::CorpseData {
                     unit: *unit,
                     translation: transform.translation,
                     rotation: rotation.0,
+                    biomass: registry.get(unit.0).nutrition,
                 }),
             });
         }
@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+
+use crate::simulation::*;
+
+const SELECTION_COLOR: Color = Color::srgb(1.0, 1.0, 0.4);
+const VISION_COLOR: Color = Color::srgba(1.0, 1.0, 0.4, 0.4);
+
+/// Unit shown in the inspector panel, chosen by clicking it.
+#[derive(Resource, Default)]
+pub struct Selection(pub Option<Entity>);
+
+#[derive(Component)]
+pub struct InspectorPanel;
+
+pub fn setup(mut commands: Commands) {
+    commands.insert_resource(Selection::default());
+
+    commands.spawn((
+        SimulationComponent,
+        InspectorPanel,
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.),
+            top: Val::Px(40.),
+            width: Val::Px(300.),
+            padding: UiRect::all(Val::Px(6.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.4)),
+        Visibility::Hidden,
+    ));
+}
+
+/// Select a unit by clicking it. Clicking the selected unit again deselects
+/// it.
+pub fn on_click_select(
+    click: Trigger<Pointer<Click>>,
+    unit_query: Query<(), With<unit::UnitType>>,
+    selection: Option<ResMut<Selection>>,
+    state: Res<State<AppState>>,
+) {
+    let (AppState::Simulation, Some(mut selection)) = (**state, selection) else {
+        return;
+    };
+    if click.button != PointerButton::Primary || !unit_query.contains(click.target()) {
+        return;
+    }
+
+    selection.0 = match selection.0 {
+        Some(selected) if selected == click.target() => None,
+        _ => Some(click.target()),
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_panel(
+    mut selection: ResMut<Selection>,
+    panel: Single<(&mut Text, &mut Visibility), With<InspectorPanel>>,
+    unit_query: Query<(
+        &unit::UnitType,
+        &unit::Lineage,
+        &Transform,
+        &hunger::Hunger,
+        &motion::MovingBody,
+        &motion::TargetPoint,
+        &brain::Target,
+        &brain::Brain,
+        &senses::Senses,
+    )>,
+    target_query: Query<
+        (
+            Option<&unit::UnitType>,
+            Option<&unit::Lineage>,
+            Option<&unit::Corpse>,
+        ),
+        With<Transform>,
+    >,
+    game_data: Res<SimData>,
+    registry: Res<SpeciesRegistry>,
+    terrain: Res<terrain::Terrain>,
+    time: Res<Time<Fixed>>,
+) {
+    let (mut text, mut visibility) = panel.into_inner();
+
+    let Some(unit) = selection.0.and_then(|entity| unit_query.get(entity).ok()) else {
+        // The selected unit has died.
+        selection.0 = None;
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+
+    let (unit, lineage, transform, hunger, moving_body, target_point, target, brain, senses) = unit;
+    let pos = transform.translation.truncate();
+    let age = (game_data.tick - lineage.born) as f32 * time.timestep().as_secs_f32();
+
+    let velocity = moving_body.curr_velocity;
+    let acceleration = moving_body.curr_acceleration;
+    let brain = match brain {
+        brain::Brain::Seeker { .. } => "seeker".to_string(),
+        brain::Brain::Wanderer { .. } => "wanderer".to_string(),
+        brain::Brain::Scripted { next, .. } => format!("scripted, waypoint {next}"),
+        brain::Brain::Neural { hidden } => format!("neural, {hidden} hidden"),
+        brain::Brain::Steering { behaviours, .. } => {
+            format!("steering, {} behaviours", behaviours.len())
+        }
+    };
+    let vision = format!(
+        "Sees {:.0} units {}{}",
+        senses.vision_radius,
+        if senses.field_of_view < 2.0 * PI {
+            format!("within {:.0}°", senses.field_of_view.to_degrees())
+        } else {
+            "all around".to_string()
+        },
+        if senses.line_of_sight {
+            ", not through obstacles"
+        } else {
+            ""
+        }
+    );
+
+    let lines = [
+        format!("{} #{}", registry.get(unit.0).name, lineage.id),
+        match lineage.parent {
+            Some(parent) => format!("Generation {}, parent #{parent}", lineage.generation),
+            None => "Generation 0, from the scenario".to_string(),
+        },
+        format!("Age {age:.1} s"),
+        format!(
+            "Position ({:.0}, {:.0}) in {}",
+            pos.x,
+            pos.y,
+            terrain.biome_at(pos)
+        ),
+        String::new(),
+        format!(
+            "Fullness {:.1} / {:.1}",
+            hunger.curr_fullness, hunger.max_fullness
+        ),
+        format!(
+            "Drain {:.3} per unit travelled",
+            hunger.drain_per_unit_traveled
+        ),
+        String::new(),
+        format!(
+            "Velocity ({:.1}, {:.1}), speed {:.1} / {:.1}",
+            velocity.x,
+            velocity.y,
+            velocity.length(),
+            moving_body.max_speed
+        ),
+        format!(
+            "Acceleration ({:.2}, {:.2}), max {:.1}",
+            acceleration.x, acceleration.y, moving_body.max_acceleration
+        ),
+        String::new(),
+        format!("Brain {brain}"),
+        vision,
+        match target_point.0 {
+            Some(point) => {
+                let point = pos + point.truncate();
+                format!("Heading for ({:.0}, {:.0})", point.x, point.y)
+            }
+            None => "Not heading anywhere".to_string(),
+        },
+        match target.0.and_then(|entity| target_query.get(entity).ok()) {
+            Some((Some(unit), Some(lineage), _)) => {
+                format!("Targeting {} #{}", registry.get(unit.0).name, lineage.id)
+            }
+            Some((_, _, Some(corpse))) => format!(
+                "Targeting a {} corpse, {:.1} biomass left",
+                registry.get(corpse.unit.0).name,
+                corpse.biomass
+            ),
+            Some(_) => "Targeting a berry".to_string(),
+            None => "Targeting nothing".to_string(),
+        },
+    ];
+
+    text.0 = lines.join("\n");
+}
+
+/// Circle the selected unit, draw a line to where it is heading and outline
+/// what it sees.
+pub fn draw_selection(
+    mut gizmos: Gizmos,
+    selection: Res<Selection>,
+    unit_query: Query<(
+        &Transform,
+        &motion::Rotation,
+        &motion::TargetPoint,
+        &unit::UnitType,
+        &senses::Senses,
+    )>,
+    registry: Res<SpeciesRegistry>,
+) {
+    let Some((transform, rotation, target_point, unit, senses)) =
+        selection.0.and_then(|entity| unit_query.get(entity).ok())
+    else {
+        return;
+    };
+
+    let pos = transform.translation.truncate();
+    let radius = (transform.scale.truncate() * registry.get(unit.0).render_size()).length() / 2.0;
+    gizmos.circle_2d(pos, radius, SELECTION_COLOR);
+    if let Some(point) = target_point.0 {
+        gizmos.line_2d(pos, pos + point.truncate(), SELECTION_COLOR);
+    }
+
+    let fov = senses.field_of_view;
+    if fov < 2.0 * PI {
+        // Arcs start at the unit's heading when it has no rotation, and go
+        // counterclockwise.
+        let start = rotation.0 - fov / 2.0;
+        gizmos.arc_2d(
+            Isometry2d::new(pos, Rot2::radians(start)),
+            fov,
+            senses.vision_radius,
+            VISION_COLOR,
+        );
+        for angle in [start, start + fov] {
+            let edge = senses.vision_radius * Vec2::new(-angle.sin(), angle.cos());
+            gizmos.line_2d(pos, pos + edge, VISION_COLOR);
+        }
+    } else {
+        gizmos.circle_2d(pos, senses.vision_radius, VISION_COLOR);
+    }
+}
@@ -1,14 +1,15 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::simulation::*;
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Rotation(pub f32);
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct TargetPoint(pub Option<Vec3>);
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct MovingBody {
     pub curr_velocity: Vec3,
     pub max_speed: f32,
@@ -16,11 +17,10 @@ pub struct MovingBody {
     pub max_acceleration: f32,
 }
 
-pub fn apply_velocity(mut query: Query<(&mut Transform, &MovingBody)>, time: Res<Time>) {
+pub fn apply_velocity(mut query: Query<(&mut Transform, &MovingBody)>, time: Res<Time<Fixed>>) {
     for (mut transform, moving_body) in query.iter_mut() {
         // Update position.
-        transform.translation =
-            transform.translation + moving_body.curr_velocity * time.delta_secs();
+        transform.translation += moving_body.curr_velocity * time.delta_secs();
     }
 }
 
@@ -39,47 +39,69 @@ pub fn apply_rotation(mut query: Query<(&mut Rotation, &mut Transform, &MovingBo
     }
 }
 
-pub fn update_velocity(mut query: Query<(&mut MovingBody, &TargetPoint)>, time: Res<Time>) {
-    for (mut moving_body, target_point) in query.iter_mut() {
-        if let Some(p) = target_point.0 {
-            moving_body.curr_acceleration = (p.normalize_or_zero() * moving_body.max_acceleration)
-                .clamp_length_max(time.delta_secs() * moving_body.max_acceleration);
-            moving_body.curr_velocity = (moving_body.curr_velocity + moving_body.curr_acceleration)
-                .clamp_length_max(moving_body.max_speed);
-        } else {
-            moving_body.curr_acceleration = -moving_body.curr_velocity
-        }
+/// Accelerate units as their brains decided, within their limits and the
+/// speed that the terrain they are on allows.
+pub fn update_velocity(
+    mut query: Query<(&Transform, &mut MovingBody)>,
+    terrain: Res<terrain::Terrain>,
+    time: Res<Time<Fixed>>,
+) {
+    for (transform, mut moving_body) in query.iter_mut() {
+        let max_speed =
+            moving_body.max_speed * terrain.effects_at(transform.translation.truncate()).speed;
+        let acceleration = moving_body
+            .curr_acceleration
+            .clamp_length_max(moving_body.max_acceleration);
+        moving_body.curr_acceleration = acceleration;
+        moving_body.curr_velocity = (moving_body.curr_velocity + acceleration * time.delta_secs())
+            .clamp_length_max(max_speed);
     }
 }
 
-pub fn repel_bodies(mut body_query: Query<(&mut Transform, &mut MovingBody)>, time: Res<Time>) {
-    let mut combinations = body_query.iter_combinations_mut();
-    while let Some([mut t1, mut t2]) = combinations.fetch_next() {
-        // Bounds before collision force is applied.
-        const COLLISION_RADIUS_SQUARED: f32 = (FERNWORM_RENDER_HEIGHT
-            + FERNWORM_RENDER_WIDTH / 2.0)
-            * (FERNWORM_RENDER_HEIGHT + FERNWORM_RENDER_WIDTH / 2.0);
-
-        // Strength of the collision force.
-        const FORCE_CONSTANT: f32 = 500000.0;
-
-        let p1: Vec3 = t1.0.as_mut().translation;
-        let p2: Vec3 = t2.0.as_mut().translation;
-        let squared_dist = p1.distance_squared(p2);
-
-        let force = if squared_dist < COLLISION_RADIUS_SQUARED {
-            FORCE_CONSTANT / (squared_dist + f32::EPSILON)
-        } else {
-            0.0
-        };
+/// Keep units within the world's bounds in the way the scenario's
+/// `scenario::Boundary` says.
+pub fn apply_boundary(
+    mut query: Query<(&mut Transform, &mut MovingBody)>,
+    scenario: Res<Scenario>,
+    time: Res<Time<Fixed>>,
+) {
+    let bounds = scenario.world.bounds;
+    let (min, max) = (bounds.min(), bounds.max());
 
-        let body1_push_dir: Vec3 = (p1 - p2).normalize_or_zero() * force * time.delta_secs();
-        let body2_push_dir: Vec3 = (p1 - p2).normalize_or_zero() * force * time.delta_secs();
+    for (mut transform, mut moving_body) in query.iter_mut() {
+        let pos = transform.translation.truncate();
 
-        let moving_body1 = t1.1.as_mut();
-        moving_body1.curr_velocity += body1_push_dir;
+        match scenario.world.boundary {
+            scenario::Boundary::Wall { restitution } => {
+                let velocity = &mut moving_body.curr_velocity;
+                if pos.x < min.x {
+                    velocity.x = velocity.x.abs() * restitution;
+                } else if pos.x > max.x {
+                    velocity.x = -velocity.x.abs() * restitution;
+                }
+                if pos.y < min.y {
+                    velocity.y = velocity.y.abs() * restitution;
+                } else if pos.y > max.y {
+                    velocity.y = -velocity.y.abs() * restitution;
+                }
 
-        let body2 = t1.1.as_mut();
-        body2.curr_velocity += body2_push_dir;
+                let pos = pos.clamp(min, max);
+                transform.translation.x = pos.x;
+                transform.translation.y = pos.y;
+            }
+            scenario::Boundary::Soft { margin, strength } => {
+                // How far the unit is into the margin along each axis, signed
+                // so that it points back inside.
+                let depth =
+                    (min + margin - pos).max(Vec2::ZERO) - (pos - max + margin).max(Vec2::ZERO);
+                moving_body.curr_velocity +=
+                    (depth / margin * strength * time.delta_secs()).extend(0.0);
+            }
+            scenario::Boundary::Wrap => {
+                let pos = scenario.world.contain(pos);
+                transform.translation.x = pos.x;
+                transform.translation.y = pos.y;
+            }
+        }
     }
 }
@@ -0,0 +1,670 @@
+use std::{cmp::Ordering, collections::BinaryHeap, f32::consts::SQRT_2};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::*;
+
+/// A static body that units cannot pass through, such as a rock, a tree or a
+/// wall.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(from = "ObstacleFile", into = "ObstacleFile")]
+pub enum Obstacle {
+    Circle {
+        center: (f32, f32),
+        radius: f32,
+    },
+    /// A convex polygon, with its corners in either order around it.
+    Polygon {
+        points: Vec<(f32, f32)>,
+        /// `points` in counter-clockwise order. Kept since every distance and
+        /// raycast goes around them.
+        corners: Vec<Vec2>,
+    },
+}
+
+/// An obstacle as written in scenario files and snapshots.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+enum ObstacleFile {
+    Circle { center: (f32, f32), radius: f32 },
+    Polygon { points: Vec<(f32, f32)> },
+}
+
+/// Where a ray first runs into an obstacle.
+#[derive(Clone, Copy)]
+pub struct Hit {
+    pub distance: f32,
+    /// Points out of the obstacle.
+    pub normal: Vec2,
+}
+
+/// Grid over the world's bounds of where units can go, for planning routes
+/// around obstacles. Cells that an obstacle covers most of are blocked, and
+/// cells within `NAV_CLEARANCE` of one are costly to pass through, so that
+/// routes keep their distance but units that are already close to an
+/// obstacle can still get away from it.
+#[derive(Resource)]
+pub struct NavGrid {
+    origin: Vec2,
+    width: usize,
+    height: usize,
+    /// Whether routes may cross the edges of a wrapping world.
+    wraps: bool,
+    /// Cost of moving through each cell per cell travelled, or `None` if it
+    /// is blocked.
+    costs: Vec<Option<f32>>,
+}
+
+/// Route of a unit around obstacles, kept from tick to tick while it leads to
+/// the same cell so that it is not planned again every tick.
+#[derive(Component, Serialize, Deserialize, Clone, Default)]
+pub struct Route {
+    /// `NavGrid` cell that the route leads to.
+    goal: Option<usize>,
+    /// Points along the route, ending in the target.
+    points: Vec<Vec2>,
+    /// Index of the point the unit heads for.
+    next: usize,
+}
+
+/// Cell of the `NavGrid` waiting to be expanded by A*, with the lowest
+/// estimated cost first.
+struct Open {
+    cost: f32,
+    cell: usize,
+}
+
+impl From<ObstacleFile> for Obstacle {
+    fn from(file: ObstacleFile) -> Self {
+        match file {
+            ObstacleFile::Circle { center, radius } => Obstacle::Circle { center, radius },
+            ObstacleFile::Polygon { points } => Obstacle::polygon(points),
+        }
+    }
+}
+
+impl From<Obstacle> for ObstacleFile {
+    fn from(obstacle: Obstacle) -> Self {
+        match obstacle {
+            Obstacle::Circle { center, radius } => ObstacleFile::Circle { center, radius },
+            Obstacle::Polygon { points, .. } => ObstacleFile::Polygon { points },
+        }
+    }
+}
+
+impl Obstacle {
+    /// A convex polygon with corners at `points`, in either order.
+    pub fn polygon(points: Vec<(f32, f32)>) -> Self {
+        let mut corners: Vec<Vec2> = points.iter().map(|&(x, y)| Vec2::new(x, y)).collect();
+        let area: f32 = edges(&corners).map(|(a, b)| a.perp_dot(b)).sum();
+        if area < 0.0 {
+            corners.reverse();
+        }
+
+        Obstacle::Polygon { points, corners }
+    }
+
+    pub fn center(&self) -> Vec2 {
+        match self {
+            Obstacle::Circle { center, .. } => Vec2::new(center.0, center.1),
+            Obstacle::Polygon { corners, .. } => {
+                corners.iter().sum::<Vec2>() / corners.len() as f32
+            }
+        }
+    }
+
+    /// Signed distance from the edge of the obstacle to `point`, negative
+    /// inside it, and the direction out of the obstacle at the nearest point
+    /// of its edge.
+    pub fn distance(&self, point: Vec2) -> (f32, Vec2) {
+        match self {
+            Obstacle::Circle { radius, .. } => {
+                let offset = point - self.center();
+                (
+                    offset.length() - radius,
+                    offset.try_normalize().unwrap_or(Vec2::X),
+                )
+            }
+            Obstacle::Polygon { corners, .. } => {
+                // Distance beyond the line through each edge.
+                let (beyond, normal) = edges(corners)
+                    .map(|(a, b)| {
+                        let normal = outward_normal(a, b);
+                        ((point - a).dot(normal), normal)
+                    })
+                    .max_by(|(a, _), (b, _)| a.total_cmp(b))
+                    .expect("polygons have corners");
+                if beyond <= 0.0 {
+                    return (beyond, normal);
+                }
+
+                let closest = edges(corners)
+                    .map(|(a, b)| closest_on_segment(point, a, b))
+                    .min_by(|a, b| {
+                        a.distance_squared(point)
+                            .total_cmp(&b.distance_squared(point))
+                    })
+                    .expect("polygons have corners");
+                let offset = point - closest;
+                (offset.length(), offset.try_normalize().unwrap_or(normal))
+            }
+        }
+    }
+
+    /// Where a ray from `origin` in the unit length `direction` first runs
+    /// into the obstacle within `max_distance`. A ray starting inside hits
+    /// right away.
+    pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<Hit> {
+        let (distance, normal) = self.distance(origin);
+        if distance <= 0.0 {
+            return Some(Hit {
+                distance: 0.0,
+                normal,
+            });
+        }
+
+        let hit = match self {
+            Obstacle::Circle { radius, .. } => {
+                let offset = origin - self.center();
+                let along = offset.dot(direction);
+                let discriminant = along * along - offset.length_squared() + radius * radius;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let distance = -along - discriminant.sqrt();
+                Hit {
+                    distance,
+                    normal: (offset + distance * direction).normalize_or(Vec2::X),
+                }
+            }
+            Obstacle::Polygon { corners, .. } => edges(corners)
+                .filter_map(|(a, b)| {
+                    let normal = outward_normal(a, b);
+                    // Only edges facing the ray can be hit from outside.
+                    let denominator = direction.perp_dot(b - a);
+                    if direction.dot(normal) >= 0.0 || denominator == 0.0 {
+                        return None;
+                    }
+                    let distance = (a - origin).perp_dot(b - a) / denominator;
+                    let along_edge = (a - origin).perp_dot(direction) / denominator;
+                    (0.0..=1.0)
+                        .contains(&along_edge)
+                        .then_some(Hit { distance, normal })
+                })
+                .min_by(|a, b| a.distance.total_cmp(&b.distance))?,
+        };
+
+        (hit.distance >= 0.0 && hit.distance <= max_distance).then_some(hit)
+    }
+
+    /// Whether the straight line from `from` to `to` passes through the
+    /// obstacle.
+    pub fn crosses(&self, from: Vec2, to: Vec2) -> bool {
+        let offset = to - from;
+        match offset.try_normalize() {
+            Some(direction) => self.raycast(from, direction, offset.length()).is_some(),
+            None => self.distance(from).0 <= 0.0,
+        }
+    }
+
+    /// Returns the name of the first invalid field and why, if any.
+    pub fn invalid_field(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                if !(center.0.is_finite() && center.1.is_finite()) {
+                    Some(("center", "must be finite numbers"))
+                } else if !(radius.is_finite() && *radius > 0.0) {
+                    Some(("radius", "must be a positive number"))
+                } else {
+                    None
+                }
+            }
+            Obstacle::Polygon { points, corners } => {
+                if points.len() < 3 {
+                    return Some(("points", "must have at least 3 corners"));
+                }
+                if !points.iter().all(|(x, y)| x.is_finite() && y.is_finite()) {
+                    return Some(("points", "must be finite numbers"));
+                }
+                let convex = (0..corners.len()).all(|i| {
+                    let [a, b, c] = [i, i + 1, i + 2].map(|j| corners[j % corners.len()]);
+                    (b - a).perp_dot(c - b) > 0.0
+                });
+                (!convex).then_some(("points", "must be the corners of a convex polygon"))
+            }
+        }
+    }
+}
+
+/// Pairs of consecutive corners, including the last and the first.
+fn edges(corners: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    corners
+        .iter()
+        .zip(corners.iter().cycle().skip(1))
+        .map(|(&a, &b)| (a, b))
+}
+
+/// Normal of the edge from `a` to `b` of a counter-clockwise polygon that
+/// points out of it.
+fn outward_normal(a: Vec2, b: Vec2) -> Vec2 {
+    -(b - a).perp().normalize_or_zero()
+}
+
+fn closest_on_segment(point: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let segment = b - a;
+    let along = (point - a).dot(segment) / segment.length_squared().max(f32::EPSILON);
+    a + along.clamp(0.0, 1.0) * segment
+}
+
+impl scenario::WorldConfig {
+    /// Signed distance from the edge of `obstacle` to `point`, and the
+    /// direction out of it, taking the shortest way across the edges of a
+    /// wrapping world.
+    pub fn obstacle_distance(&self, obstacle: &Obstacle, point: Vec2) -> (f32, Vec2) {
+        let center = obstacle.center();
+        obstacle.distance(center + self.offset(center, point))
+    }
+
+    pub fn is_inside_obstacle(&self, point: Vec2) -> bool {
+        self.obstacles
+            .iter()
+            .any(|obstacle| self.obstacle_distance(obstacle, point).0 < 0.0)
+    }
+
+    /// Whether the straight line from `from` to `from + offset` is free of
+    /// obstacles.
+    pub fn is_clear(&self, from: Vec2, offset: Vec2) -> bool {
+        self.obstacles.iter().all(|obstacle| {
+            let center = obstacle.center();
+            let from = center + self.offset(center, from);
+            !obstacle.crosses(from, from + offset)
+        })
+    }
+
+    /// Draw positions with `draw` until one is outside all obstacles, giving
+    /// up after `SPAWN_ATTEMPTS`.
+    pub fn free_position(
+        &self,
+        rng: &mut rng::SimRng,
+        mut draw: impl FnMut(&mut rng::SimRng) -> Vec2,
+    ) -> Vec2 {
+        let mut position = draw(rng);
+        for _ in 1..SPAWN_ATTEMPTS {
+            if !self.is_inside_obstacle(position) {
+                break;
+            }
+            position = draw(rng);
+        }
+
+        position
+    }
+
+    /// Random position within the bounds that is outside all obstacles.
+    pub fn random_position(&self, rng: &mut rng::SimRng) -> Vec2 {
+        let bounds = self.bounds;
+        self.free_position(rng, |rng| {
+            Vec2::new(
+                rng.0.gen_range(bounds.x0..bounds.x1),
+                rng.0.gen_range(bounds.y0..bounds.y1),
+            )
+        })
+    }
+
+    /// The nearest obstacle that a body of `radius` at `position` would run
+    /// into when moving `distance` in `direction`. The distance of the hit is
+    /// measured from the front of the body.
+    pub fn look_ahead(
+        &self,
+        position: Vec2,
+        direction: Vec2,
+        radius: f32,
+        distance: f32,
+    ) -> Option<Hit> {
+        let side = radius * direction.perp();
+
+        self.obstacles
+            .iter()
+            .flat_map(|obstacle| {
+                let center = obstacle.center();
+                // One ray from the middle of the body and one from each side.
+                [-side, Vec2::ZERO, side].map(|side| {
+                    let origin = center + self.offset(center, position + side);
+                    obstacle.raycast(origin, direction, radius + distance)
+                })
+            })
+            .flatten()
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+            .map(|hit| Hit {
+                distance: (hit.distance - radius).max(0.0),
+                ..hit
+            })
+    }
+}
+
+impl NavGrid {
+    pub fn new(world: &scenario::WorldConfig) -> Self {
+        let size = world.bounds.size();
+        let width = (size.x / NAV_CELL_SIZE).ceil().max(1.0) as usize;
+        let height = (size.y / NAV_CELL_SIZE).ceil().max(1.0) as usize;
+        let origin = world.bounds.min();
+
+        let mut grid = NavGrid {
+            origin,
+            width,
+            height,
+            wraps: matches!(world.boundary, scenario::Boundary::Wrap),
+            costs: vec![Some(1.0); width * height],
+        };
+        for cell in 0..width * height {
+            let center = grid.center(cell);
+            let distance = world
+                .obstacles
+                .iter()
+                .map(|obstacle| world.obstacle_distance(obstacle, center).0)
+                .fold(f32::INFINITY, f32::min);
+            grid.costs[cell] = if distance < NAV_CELL_SIZE / 2.0 {
+                None
+            } else if distance < NAV_CLEARANCE {
+                Some(NAV_NEAR_OBSTACLE_COST)
+            } else {
+                Some(1.0)
+            };
+        }
+
+        grid
+    }
+
+    fn cell(&self, position: Vec2) -> usize {
+        let cell = ((position - self.origin) / NAV_CELL_SIZE).floor();
+        let (x, y) = if self.wraps {
+            (
+                cell.x.rem_euclid(self.width as f32) as usize,
+                cell.y.rem_euclid(self.height as f32) as usize,
+            )
+        } else {
+            (cell.x.max(0.0) as usize, cell.y.max(0.0) as usize)
+        };
+
+        y.min(self.height - 1) * self.width + x.min(self.width - 1)
+    }
+
+    /// Number of cells between `a` and `b` along each axis, going across the
+    /// edges if the grid wraps and that is shorter.
+    fn cells_between(&self, a: usize, b: usize) -> Vec2 {
+        let size = Vec2::new(self.width as f32, self.height as f32);
+        let offset = ((self.center(b) - self.center(a)) / NAV_CELL_SIZE).abs();
+        if self.wraps {
+            offset.min(size - offset)
+        } else {
+            offset
+        }
+    }
+
+    fn center(&self, cell: usize) -> Vec2 {
+        let (x, y) = (cell % self.width, cell / self.width);
+        self.origin + (Vec2::new(x as f32, y as f32) + 0.5) * NAV_CELL_SIZE
+    }
+
+    /// Cells next to `cell` that can be moved to, with the cost of moving
+    /// there. Moving diagonally past the corner of a blocked cell is not
+    /// allowed.
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let (x, y) = ((cell % self.width) as isize, (cell / self.width) as isize);
+        let (width, height) = (self.width as isize, self.height as isize);
+        let free = move |dx: isize, dy: isize| {
+            let (x, y) = if self.wraps {
+                ((x + dx).rem_euclid(width), (y + dy).rem_euclid(height))
+            } else {
+                (x + dx, y + dy)
+            };
+            let inside = x >= 0 && y >= 0 && x < width && y < height;
+            let cell = (y * self.width as isize + x) as usize;
+            inside
+                .then(|| self.costs[cell].map(|cost| (cell, cost)))
+                .flatten()
+        };
+
+        [
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let (cell, cost) = free(dx, dy)?;
+            if dx != 0 && dy != 0 && (free(dx, 0).is_none() || free(0, dy).is_none()) {
+                return None;
+            }
+            let distance = if dx != 0 && dy != 0 { SQRT_2 } else { 1.0 };
+            Some((cell, distance * cost))
+        })
+    }
+
+    /// Cheapest route from `from` to `to` through cells that are not blocked,
+    /// found with A*, as the centers of the cells along it after the first,
+    /// ending in `to`. The cells of `from` and `to` may be blocked themselves.
+    pub fn path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let (start, goal) = (self.cell(from), self.cell(to));
+        // Octile distance in cells, which never overestimates since no cell
+        // costs less than 1.
+        let estimate = |cell: usize| {
+            let offset = self.cells_between(cell, goal);
+            offset.max_element() + (SQRT_2 - 1.0) * offset.min_element()
+        };
+
+        let mut cost = vec![f32::INFINITY; self.costs.len()];
+        let mut came_from = vec![usize::MAX; self.costs.len()];
+        let mut closed = vec![false; self.costs.len()];
+        let mut open = BinaryHeap::from([Open {
+            cost: estimate(start),
+            cell: start,
+        }]);
+        cost[start] = 0.0;
+
+        while let Some(Open { cell, .. }) = open.pop() {
+            if closed[cell] {
+                continue;
+            }
+            closed[cell] = true;
+            if cell == goal {
+                let mut path = vec![to];
+                let mut cell = came_from[goal];
+                while cell != start && cell != usize::MAX {
+                    path.push(self.center(cell));
+                    cell = came_from[cell];
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let mut neighbours: Vec<_> = self.neighbours(cell).collect();
+            // The goal may be blocked, but it can still be reached.
+            if goal != cell && self.costs[goal].is_none() {
+                let offset = self.cells_between(cell, goal);
+                if offset.max_element() < 1.5 {
+                    neighbours.push((goal, offset.length()));
+                }
+            }
+
+            for (next, step) in neighbours {
+                let next_cost = cost[cell] + step;
+                if next_cost < cost[next] {
+                    cost[next] = next_cost;
+                    came_from[next] = cell;
+                    open.push(Open {
+                        cost: next_cost + estimate(next),
+                        cell: next,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    /// Reversed, so that `BinaryHeap` pops the lowest cost first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.cell.cmp(&self.cell))
+    }
+}
+
+/// Offset from `from` to head for on the way to `from + offset`: straight
+/// there if nothing is in the way, otherwise the furthest point of a route
+/// around the obstacles that can be seen from `from`.
+///
+/// The unit's last `route` is followed on while it leads to the same cell and
+/// the point it headed for is still in sight. Otherwise a new one is planned.
+pub fn route(
+    world: &scenario::WorldConfig,
+    nav: &NavGrid,
+    route: &mut Route,
+    from: Vec2,
+    offset: Vec2,
+) -> Vec2 {
+    if world.is_clear(from, offset) {
+        return offset;
+    }
+
+    let to = from + offset;
+    let goal = nav.cell(to);
+    let lost = route
+        .points
+        .get(route.next)
+        .is_none_or(|point| !world.is_clear(from, world.offset(from, *point)));
+    if route.goal != Some(goal) || lost {
+        let Some(points) = nav.path(from, to) else {
+            *route = Route::default();
+            return offset;
+        };
+        *route = Route {
+            goal: Some(goal),
+            points,
+            next: 0,
+        };
+    }
+    // The target may have moved within its cell.
+    if let Some(last) = route.points.last_mut() {
+        *last = to;
+    }
+
+    while let Some(point) = route.points.get(route.next + 1) {
+        if !world.is_clear(from, world.offset(from, *point)) {
+            break;
+        }
+        route.next += 1;
+    }
+
+    world.offset(from, route.points[route.next])
+}
+
+/// Build the navigation grid of the scenario that is being simulated.
+pub fn build_nav_grid(mut commands: Commands, scenario: Res<Scenario>) {
+    commands.insert_resource(NavGrid::new(&scenario.world));
+}
+
+/// Stop units from moving into obstacles. Units that overlap one are moved
+/// out of it and lose the part of their velocity that points into it, so
+/// that they slide along its edge.
+pub fn resolve_obstacles(
+    mut query: Query<(&mut Transform, &mut motion::MovingBody, &unit::UnitType)>,
+    scenario: Res<Scenario>,
+    registry: Res<SpeciesRegistry>,
+) {
+    let world = &scenario.world;
+
+    for (mut transform, mut moving_body, unit) in query.iter_mut() {
+        let radius = collision::body_radius(registry.get(unit.0), transform.scale.x);
+
+        for obstacle in &world.obstacles {
+            let (distance, normal) =
+                world.obstacle_distance(obstacle, transform.translation.truncate());
+            if distance >= radius {
+                continue;
+            }
+
+            transform.translation += ((radius - distance) * normal).extend(0.0);
+            let velocity = moving_body.curr_velocity.truncate();
+            let into = velocity.dot(normal);
+            if into < 0.0 {
+                moving_body.curr_velocity -= (into * normal).extend(0.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 500 by 500 world split by a wall across its whole height, which can
+    /// only be got round across the edges of the world.
+    fn split_world(boundary: &str) -> scenario::WorldConfig {
+        ron::from_str(&format!(
+            "(
+                bounds: (x0: 0.0, x1: 500.0, y0: 0.0, y1: 500.0),
+                boundary: {boundary},
+                obstacles: [
+                    Polygon(points: [(240.0, -50.0), (260.0, -50.0), (260.0, 550.0), (240.0, 550.0)]),
+                ],
+            )"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn paths_cross_wrapping_edges() {
+        let (from, to) = (Vec2::new(200.0, 250.0), Vec2::new(300.0, 250.0));
+
+        let walled = split_world("Wall(restitution: 0.5)");
+        assert!(NavGrid::new(&walled).path(from, to).is_none());
+
+        let wrapped = split_world("Wrap");
+        let path = NavGrid::new(&wrapped).path(from, to).unwrap();
+        // Round the short way, through the cells next to the left edge.
+        assert!(path.len() < 20, "{} points", path.len());
+        assert!(path.iter().any(|point| point.x < NAV_CELL_SIZE));
+    }
+
+    #[test]
+    fn routes_are_kept_while_the_goal_stays() {
+        let world = split_world("Wrap");
+        let nav = NavGrid::new(&world);
+        let mut route = Route::default();
+        let from = Vec2::new(200.0, 250.0);
+
+        let offset = super::route(&world, &nav, &mut route, from, Vec2::new(100.0, 0.0));
+        assert!(offset.x < 0.0, "{offset}");
+        let points = route.points.clone();
+
+        // Within the same cell as before.
+        let offset = super::route(&world, &nav, &mut route, from, Vec2::new(102.0, 1.0));
+        assert!(offset.x < 0.0, "{offset}");
+        assert_eq!(route.points[..points.len() - 1], points[..points.len() - 1]);
+        assert_eq!(route.points.last(), Some(&Vec2::new(302.0, 251.0)));
+    }
+}
@@ -0,0 +1,329 @@
+use bevy::{platform::collections::HashMap, prelude::*, ui::RelativeCursorPosition};
+
+use crate::simulation::{
+    presentation::{
+        background_sprite, berry_sprite, bush_sprite, clock, corpse_sprite, unit_sprite,
+    },
+    replay::{Pose, Replay, ReplayKind},
+    *,
+};
+
+/// Slowest and fastest playback speed, relative to the recorded tick rate.
+const MIN_SPEED: f32 = 0.125;
+const MAX_SPEED: f32 = 256.0;
+
+/// Seconds of simulated time that the arrow keys jump.
+const JUMP_SECS: f64 = 10.0;
+
+const BERRY_DEPTH: f32 = 1.0;
+
+/// Use this component for entities that should be despawned upon state exit.
+#[derive(Component)]
+struct PlaybackComponent;
+
+/// Sprite showing a replay entity.
+#[derive(Component)]
+struct ReplaySprite;
+
+#[derive(Component)]
+struct PlaybackText;
+
+/// Bar at the bottom of the screen that the playhead can be dragged along.
+#[derive(Component)]
+struct Timeline;
+
+/// Part of the `Timeline` that has been played.
+#[derive(Component)]
+struct TimelineProgress;
+
+#[derive(Resource)]
+struct Playhead {
+    tick: f64,
+    speed: f32,
+    paused: bool,
+    /// Frame whose entities currently have sprites.
+    shown: Option<usize>,
+    sprites: HashMap<u32, Entity>,
+    /// Poses in the frame after the shown one, to interpolate towards.
+    next_poses: HashMap<u32, Pose>,
+}
+
+/// Plays back a `Replay` recorded with `--record`. Space pauses, the up and
+/// down arrows change the speed, left and right jump, comma and period step a
+/// single frame and dragging along the timeline scrubs.
+pub fn playback_plugin(app: &mut App) {
+    app.add_systems(OnEnter(AppState::Replay), setup)
+        .add_systems(OnExit(AppState::Replay), exit)
+        .add_systems(
+            Update,
+            (
+                handle_input,
+                scrub_timeline,
+                advance_playhead,
+                show_frame,
+                update_text,
+            )
+                .chain()
+                .run_if(in_state(AppState::Replay)),
+        );
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, replay: Res<Replay>) {
+    commands.insert_resource(Playhead {
+        tick: replay.frames[0].tick as f64,
+        speed: 1.0,
+        paused: false,
+        shown: None,
+        sprites: HashMap::default(),
+        next_poses: HashMap::default(),
+    });
+
+    commands.spawn((PlaybackComponent, background_sprite(&asset_server)));
+
+    commands.spawn((
+        PlaybackComponent,
+        PlaybackText,
+        Text::new(""),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            bottom: Val::Px(30.),
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        PlaybackComponent,
+        Timeline,
+        Interaction::default(),
+        RelativeCursorPosition::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            right: Val::Px(10.),
+            bottom: Val::Px(10.),
+            height: Val::Px(12.),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.15, 0.15, 0.15, 0.8)),
+        children![(
+            TimelineProgress,
+            Node {
+                width: Val::Percent(0.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.9, 0.9, 0.9)),
+        )],
+    ));
+}
+
+fn exit(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<PlaybackComponent>, With<ReplaySprite>)>>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<Playhead>();
+}
+
+fn handle_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut playhead: ResMut<Playhead>,
+    mut state: ResMut<NextState<AppState>>,
+    replay: Res<Replay>,
+) {
+    let first = replay.frames[0].tick as f64;
+    let last = replay.frames[replay.frames.len() - 1].tick as f64;
+    let jump_ticks = JUMP_SECS / replay.tick_secs as f64;
+    let frame = frame_at(&replay, playhead.tick);
+
+    for key in keyboard_input.get_just_pressed() {
+        match key {
+            KeyCode::Escape => state.set(AppState::Menu),
+            KeyCode::Space => playhead.paused = !playhead.paused,
+            KeyCode::ArrowUp => playhead.speed = (playhead.speed * 2.0).min(MAX_SPEED),
+            KeyCode::ArrowDown => playhead.speed = (playhead.speed / 2.0).max(MIN_SPEED),
+            KeyCode::ArrowLeft => playhead.tick -= jump_ticks,
+            KeyCode::ArrowRight => playhead.tick += jump_ticks,
+            KeyCode::Home => playhead.tick = first,
+            KeyCode::End => playhead.tick = last,
+            KeyCode::Comma => {
+                playhead.paused = true;
+                playhead.tick = replay.frames[frame.saturating_sub(1)].tick as f64;
+            }
+            KeyCode::Period => {
+                playhead.paused = true;
+                playhead.tick = replay.frames[(frame + 1).min(replay.frames.len() - 1)].tick as f64;
+            }
+            _ => {}
+        }
+    }
+
+    playhead.tick = playhead.tick.clamp(first, last);
+}
+
+fn scrub_timeline(
+    query: Query<(&Interaction, &RelativeCursorPosition), With<Timeline>>,
+    mut playhead: ResMut<Playhead>,
+    replay: Res<Replay>,
+) {
+    for (interaction, cursor) in &query {
+        if let (Interaction::Pressed, Some(position)) = (interaction, cursor.normalized) {
+            let first = replay.frames[0].tick as f64;
+            let last = replay.frames[replay.frames.len() - 1].tick as f64;
+            playhead.tick = first + (last - first) * position.x.clamp(0.0, 1.0) as f64;
+        }
+    }
+}
+
+fn advance_playhead(mut playhead: ResMut<Playhead>, replay: Res<Replay>, time: Res<Time>) {
+    if playhead.paused {
+        return;
+    }
+
+    let last = replay.frames[replay.frames.len() - 1].tick as f64;
+    let ticks = time.delta_secs_f64() * playhead.speed as f64 / replay.tick_secs as f64;
+    playhead.tick = (playhead.tick + ticks).min(last);
+}
+
+/// Index of the last frame at or before `tick`.
+fn frame_at(replay: &Replay, tick: f64) -> usize {
+    replay
+        .frames
+        .partition_point(|frame| frame.tick as f64 <= tick)
+        .saturating_sub(1)
+}
+
+fn show_frame(
+    mut commands: Commands,
+    mut playhead: ResMut<Playhead>,
+    mut sprite_query: Query<&mut Transform, With<ReplaySprite>>,
+    replay: Res<Replay>,
+    registry: Res<SpeciesRegistry>,
+    asset_server: Res<AssetServer>,
+) {
+    let playhead = playhead.as_mut();
+    let index = frame_at(&replay, playhead.tick);
+    let frame = &replay.frames[index];
+
+    if playhead.shown != Some(index) {
+        playhead.shown = Some(index);
+        let next = replay.frames.get(index + 1).unwrap_or(frame);
+        playhead.next_poses = next.units.iter().copied().collect();
+
+        let alive: Vec<u32> = (0..replay.entities.len() as u32)
+            .filter(|id| replay.entities[*id as usize].alive_in(index))
+            .collect();
+
+        playhead.sprites.retain(|id, sprite| {
+            let keep = replay.entities[*id as usize].alive_in(index);
+            if !keep {
+                commands.entity(*sprite).despawn();
+            }
+            keep
+        });
+
+        for id in alive {
+            playhead.sprites.entry(id).or_insert_with(|| {
+                let entity = &replay.entities[id as usize];
+                let (sprite, depth) = match entity.kind {
+                    ReplayKind::Unit(species) => {
+                        let species = registry.get(species);
+                        (unit_sprite(&asset_server, species), species.depth)
+                    }
+                    ReplayKind::Berry => (berry_sprite(&asset_server), BERRY_DEPTH),
+                    ReplayKind::Corpse(species) => {
+                        let species = registry.get(species);
+                        (corpse_sprite(&asset_server, species), species.depth)
+                    }
+                    ReplayKind::Bush => (bush_sprite(&asset_server), BUSH_DEPTH),
+                };
+
+                commands
+                    .spawn((
+                        ReplaySprite,
+                        sprite,
+                        pose_transform(&entity.pose, depth, entity.scale),
+                    ))
+                    .id()
+            });
+        }
+    }
+
+    // Interpolate units between this frame and the next.
+    let next_tick = replay
+        .frames
+        .get(index + 1)
+        .map_or(frame.tick, |next| next.tick);
+    let alpha = if next_tick > frame.tick {
+        ((playhead.tick - frame.tick as f64) / (next_tick - frame.tick) as f64) as f32
+    } else {
+        0.0
+    };
+
+    for (id, pose) in &frame.units {
+        let Some(sprite) = playhead.sprites.get(id) else {
+            continue;
+        };
+        let Ok(mut transform) = sprite_query.get_mut(*sprite) else {
+            // Spawned this update.
+            continue;
+        };
+
+        // A unit that moved further than half the world wrapped around it, so
+        // jump instead of sliding across the screen.
+        let next = playhead
+            .next_poses
+            .get(id)
+            .filter(|next| {
+                let moved = (next.position - pose.position).abs();
+                !replay.wraps || moved.cmplt(replay.bounds.size() / 2.0).all()
+            })
+            .unwrap_or(pose);
+        let position = pose.position.lerp(next.position, alpha);
+        transform.translation = position.extend(transform.translation.z);
+        transform.rotation =
+            Quat::from_rotation_z(pose.rotation).slerp(Quat::from_rotation_z(next.rotation), alpha);
+    }
+}
+
+fn pose_transform(pose: &Pose, depth: f32, scale: f32) -> Transform {
+    Transform {
+        translation: pose.position.extend(depth),
+        rotation: Quat::from_rotation_z(pose.rotation),
+        scale: Vec3::splat(scale),
+    }
+}
+
+fn update_text(
+    mut text: Single<&mut Text, With<PlaybackText>>,
+    mut progress: Single<&mut Node, With<TimelineProgress>>,
+    playhead: Res<Playhead>,
+    replay: Res<Replay>,
+) {
+    let first = replay.frames[0].tick as f64;
+    let last = replay.frames[replay.frames.len() - 1].tick as f64;
+    let fraction = if last > first {
+        (playhead.tick - first) / (last - first)
+    } else {
+        1.0
+    };
+    progress.width = Val::Percent(100.0 * fraction as f32);
+
+    let secs = playhead.tick * replay.tick_secs as f64;
+    let total_secs = last * replay.tick_secs as f64;
+    let state = if playhead.paused { "paused" } else { "playing" };
+    text.0 = format!(
+        "tick {:.0}  {}/{}  {}x  {state}",
+        playhead.tick,
+        clock(secs),
+        clock(total_secs),
+        playhead.speed
+    );
+}
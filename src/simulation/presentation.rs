@@ -0,0 +1,368 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+    time::TimeSystem,
+};
+
+use crate::simulation::*;
+
+const OBSTACLE_COLOR: Color = Color::srgb(0.35, 0.32, 0.28);
+
+/// Translation of a unit at the two most recent simulation ticks. The
+/// displayed translation is interpolated between them, so that movement looks
+/// smooth regardless of how the tick rate relates to the frame rate.
+#[derive(Component)]
+pub struct Interpolated {
+    previous: Vec3,
+    /// Translation that the simulation left the unit at.
+    pub current: Vec3,
+}
+
+/// Rendering and interaction on top of the core simulation. Attaches sprites
+/// to the entities spawned by `simulation_plugin` and handles user input.
+pub fn presentation_plugin(app: &mut App) {
+    app.add_systems(
+        OnEnter(AppState::Simulation),
+        (setup, speed::setup, hud::setup, inspect::setup),
+    )
+    .add_systems(OnExit(AppState::Simulation), speed::exit)
+    .add_systems(
+        First,
+        speed::apply_speed
+            .after(TimeSystem)
+            .run_if(in_state(AppState::Simulation)),
+    )
+    .add_systems(
+        RunFixedMainLoop,
+        (
+            restore_simulated_translation
+                .in_set(RunFixedMainLoopSystem::BeforeFixedMainLoop)
+                .run_if(in_state(AppState::Simulation)),
+            interpolate_translation
+                .in_set(RunFixedMainLoopSystem::AfterFixedMainLoop)
+                .run_if(in_state(AppState::Simulation)),
+        ),
+    )
+    .add_systems(
+        FixedFirst,
+        store_previous_translation.run_if(in_state(AppState::Simulation)),
+    )
+    .add_systems(
+        FixedLast,
+        (store_current_translation, hud::sample_history).run_if(in_state(AppState::Simulation)),
+    )
+    .add_systems(
+        Update,
+        (
+            handle_input,
+            add_unit_sprites,
+            add_berry_sprites,
+            add_bush_sprites,
+            add_corpse_sprites,
+            add_terrain_sprite.run_if(resource_changed::<terrain::Terrain>),
+            add_obstacle_meshes.run_if(resource_changed::<obstacle::NavGrid>),
+            speed::update_speed_text,
+            hud::update_hud_text,
+            hud::toggle_hud,
+            inspect::update_panel,
+            inspect::draw_selection,
+            camera::toggle_follow,
+            camera::follow_selection.after(inspect::update_panel),
+        )
+            .run_if(in_state(AppState::Simulation)),
+    )
+    .add_systems(
+        Update,
+        (camera::zoom_camera, camera::fit_playable_area)
+            .run_if(in_state(AppState::Simulation).or(in_state(AppState::Replay))),
+    )
+    .init_resource::<camera::CameraFollow>()
+    .add_observer(on_drag_move_screen)
+    .add_observer(inspect::on_click_select);
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((SimulationComponent, background_sprite(&asset_server)));
+}
+
+pub fn background_sprite(asset_server: &AssetServer) -> Sprite {
+    Sprite {
+        image: asset_server.load("sprites/forest.png"),
+        custom_size: Some(Vec2::new(SCREEN_WIDTH, SCREEN_HEIGHT)),
+        ..default()
+    }
+}
+
+/// The terrain's map, stretched over the world's bounds.
+fn terrain_sprite(asset_server: &AssetServer, map: &str, bounds: &scenario::Bounds) -> Sprite {
+    Sprite {
+        image: asset_server.load(map),
+        custom_size: Some(bounds.size()),
+        ..default()
+    }
+}
+
+pub fn unit_sprite(asset_server: &AssetServer, species: &Species) -> Sprite {
+    Sprite {
+        image: asset_server.load(&species.sprite),
+        custom_size: Some(species.render_size()),
+        ..default()
+    }
+}
+
+pub fn berry_sprite(asset_server: &AssetServer) -> Sprite {
+    Sprite {
+        image: asset_server.load("sprites/berry.png"),
+        custom_size: Some(Vec2::new(BERRY_RENDER_WIDTH, BERRY_RENDER_HEIGHT)),
+        ..default()
+    }
+}
+
+pub fn bush_sprite(asset_server: &AssetServer) -> Sprite {
+    Sprite {
+        image: asset_server.load("sprites/bush.png"),
+        custom_size: Some(Vec2::splat(BUSH_RENDER_SIZE)),
+        ..default()
+    }
+}
+
+pub fn corpse_sprite(asset_server: &AssetServer, species: &Species) -> Sprite {
+    Sprite {
+        image: asset_server.load(&species.corpse_sprite),
+        custom_size: Some(species.render_size()),
+        ..default()
+    }
+}
+
+/// Format seconds as hours, minutes and seconds.
+pub fn clock(secs: f64) -> String {
+    let secs = secs as u64;
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn add_unit_sprites(
+    mut commands: Commands,
+    query: Query<(Entity, &unit::UnitType, &Transform), Added<unit::UnitType>>,
+    asset_server: Res<AssetServer>,
+    registry: Res<SpeciesRegistry>,
+) {
+    for (entity, unit, transform) in query.iter() {
+        let species = registry.get(unit.0);
+
+        commands.entity(entity).insert((
+            unit_sprite(&asset_server, species),
+            Interpolated {
+                previous: transform.translation,
+                current: transform.translation,
+            },
+        ));
+    }
+}
+
+fn add_berry_sprites(
+    mut commands: Commands,
+    query: Query<Entity, Added<berry::Berry>>,
+    asset_server: Res<AssetServer>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(berry_sprite(&asset_server));
+    }
+}
+
+fn add_bush_sprites(
+    mut commands: Commands,
+    query: Query<Entity, Added<berry::Bush>>,
+    asset_server: Res<AssetServer>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(bush_sprite(&asset_server));
+    }
+}
+
+fn add_corpse_sprites(
+    mut commands: Commands,
+    query: Query<(Entity, &unit::Corpse), Added<unit::Corpse>>,
+    asset_server: Res<AssetServer>,
+    registry: Res<SpeciesRegistry>,
+) {
+    for (entity, corpse) in query.iter() {
+        let species = registry.get(corpse.unit.0);
+        commands
+            .entity(entity)
+            .insert(corpse_sprite(&asset_server, species));
+    }
+}
+
+/// Draw the scenario's obstacles once their navigation grid is built, which
+/// is when the simulation's scenario is known.
+fn add_obstacle_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    scenario: Res<Scenario>,
+) {
+    let material = materials.add(OBSTACLE_COLOR);
+
+    for obstacle in &scenario.world.obstacles {
+        let center = obstacle.center();
+        let mesh = match obstacle {
+            obstacle::Obstacle::Circle { radius, .. } => Mesh::from(Circle::new(*radius)),
+            obstacle::Obstacle::Polygon { corners, .. } => {
+                // A fan of counter-clockwise triangles around the corners,
+                // which covers the polygon since it is convex.
+                let positions: Vec<[f32; 3]> = corners
+                    .iter()
+                    .map(|corner| (*corner - center).extend(0.0).to_array())
+                    .collect();
+                let indices = (1..corners.len() as u32 - 1)
+                    .flat_map(|i| [0, i, i + 1])
+                    .collect();
+                Mesh::new(
+                    PrimitiveTopology::TriangleList,
+                    RenderAssetUsages::RENDER_WORLD,
+                )
+                .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+                .with_inserted_indices(Indices::U32(indices))
+            }
+        };
+
+        commands.spawn((
+            SimulationComponent,
+            Mesh2d(meshes.add(mesh)),
+            MeshMaterial2d(material.clone()),
+            Transform::from_translation(center.extend(OBSTACLE_DEPTH)),
+        ));
+    }
+}
+
+/// Draw the map of the terrain on top of the background.
+fn add_terrain_sprite(
+    mut commands: Commands,
+    terrain: Res<terrain::Terrain>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(map) = &terrain.map else {
+        return;
+    };
+    let bounds = terrain.bounds();
+
+    commands.spawn((
+        SimulationComponent,
+        terrain_sprite(&asset_server, map, &bounds),
+        Transform::from_translation(((bounds.min() + bounds.max()) / 2.0).extend(TERRAIN_DEPTH)),
+    ));
+}
+
+/// Put back the translation from the last tick before the simulation runs, so
+/// that it never sees an interpolated translation.
+fn restore_simulated_translation(mut query: Query<(&mut Transform, &Interpolated)>) {
+    for (mut transform, interpolated) in query.iter_mut() {
+        transform.translation = interpolated.current;
+    }
+}
+
+fn store_previous_translation(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.previous = transform.translation;
+    }
+}
+
+fn store_current_translation(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.current = transform.translation;
+    }
+}
+
+fn interpolate_translation(
+    mut query: Query<(&mut Transform, &Interpolated)>,
+    scenario: Res<Scenario>,
+    time: Res<Time<Fixed>>,
+) {
+    let alpha = time.overstep_fraction();
+    for (mut transform, interpolated) in query.iter_mut() {
+        let (previous, current) = (interpolated.previous, interpolated.current);
+        let moved = (current - previous).truncate();
+
+        // Do not slide across the screen after wrapping around the world.
+        transform.translation = if scenario.world.offset(Vec2::ZERO, moved) == moved {
+            previous.lerp(current, alpha)
+        } else {
+            current
+        };
+    }
+}
+
+fn handle_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<NextState<AppState>>,
+    mut write_stats: EventWriter<stats::WriteStats>,
+    mut save_snapshot: EventWriter<snapshot::SaveSnapshot>,
+    mut speed: ResMut<speed::SimSpeed>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        state.set(AppState::Menu);
+    }
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        write_stats.write(stats::WriteStats);
+    }
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        save_snapshot.write(snapshot::SaveSnapshot);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        speed.paused = !speed.paused;
+    }
+    // Step a single tick, pausing first if running.
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        if speed.paused {
+            speed.steps += 1;
+        } else {
+            speed.paused = true;
+        }
+    }
+
+    let digits = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+    ];
+    for (key, scale) in digits.into_iter().zip(SPEED_STEPS) {
+        if keyboard_input.just_pressed(key) {
+            speed.setting = speed::SpeedSetting::Scaled(scale);
+            speed.paused = false;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit5) {
+        speed.setting = speed::SpeedSetting::Max;
+        speed.paused = false;
+    }
+}
+
+fn on_drag_move_screen(
+    drag: Trigger<Pointer<Drag>>,
+    camera: Single<(&mut Transform, &Projection), With<Camera2d>>,
+    mut follow: ResMut<camera::CameraFollow>,
+    state: Res<State<AppState>>,
+    ui_query: Query<(), With<Node>>,
+) {
+    // Currently no way to only trigger the observer in one state. We have to explicitly check the state here.
+    if !matches!(**state, AppState::Simulation | AppState::Replay) {
+        return;
+    }
+    // Dragging on the UI, such as the replay timeline, should not move the screen.
+    if ui_query.contains(drag.target()) {
+        return;
+    }
+
+    // Move as far in the world as the pointer moved on the screen.
+    let (mut transform, projection) = camera.into_inner();
+    let scale = match projection {
+        Projection::Orthographic(orthographic) => orthographic.scale,
+        _ => 1.0,
+    };
+    follow.0 = false;
+    transform.translation.x -= drag.delta.x * scale;
+    transform.translation.y += drag.delta.y * scale;
+}
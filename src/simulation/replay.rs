@@ -0,0 +1,539 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+
+use crate::simulation::*;
+
+const MAGIC: &[u8; 4] = b"ECOR";
+const VERSION: u32 = 2;
+
+/// File that the run is recorded to.
+#[derive(Resource)]
+pub struct ReplayFile(pub PathBuf);
+
+/// What kind of entity a replay entity is.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ReplayKind {
+    Unit(species::SpeciesId),
+    Berry,
+    Corpse(species::SpeciesId),
+    Bush,
+}
+
+#[derive(Clone, Copy)]
+pub struct Pose {
+    pub position: Vec2,
+    pub rotation: f32,
+}
+
+/// Writes a log of spawns, despawns and unit positions every
+/// `REPLAY_INTERVAL` ticks. Bushes, berries and corpses never move, so their
+/// position is only written when they spawn.
+///
+/// The file starts with a header, followed by one frame per sample:
+///
+/// - header: magic, version, seconds per tick, world bounds, whether the world
+///   wraps, species names
+/// - frame: tick, despawned ids, spawned entities with their kind, scale and
+///   pose, and the pose of every unit
+///
+/// Every frame is flushed once written, so a run that is killed loses at most
+/// the frame it was writing.
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    out: BufWriter<File>,
+    path: PathBuf,
+    ids: EntityHashMap<u32>,
+    next_id: u32,
+}
+
+/// A recorded run, loaded into memory for playback.
+#[derive(Resource)]
+pub struct Replay {
+    pub tick_secs: f32,
+    /// Bounds of the recorded world.
+    pub bounds: scenario::Bounds,
+    /// Whether units leaving the recorded world came back on the other side.
+    pub wraps: bool,
+    pub frames: Vec<ReplayFrame>,
+    /// Indexed by replay id.
+    pub entities: Vec<ReplayEntity>,
+}
+
+pub struct ReplayFrame {
+    pub tick: u64,
+    /// Replay id and pose of every unit alive in this frame.
+    pub units: Vec<(u32, Pose)>,
+}
+
+pub struct ReplayEntity {
+    pub kind: ReplayKind,
+    pub scale: f32,
+    /// Pose when spawned. Only units ever move.
+    pub pose: Pose,
+    /// Frame in which the entity first appears.
+    pub spawned: usize,
+    /// Frame in which the entity is gone, if it is gone before the end.
+    pub despawned: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(PathBuf, io::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            ReplayError::Invalid(path, reason) => write!(f, "{}: {reason}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl ReplayEntity {
+    pub fn alive_in(&self, frame: usize) -> bool {
+        self.spawned <= frame && self.despawned.is_none_or(|despawned| frame < despawned)
+    }
+}
+
+impl ReplayRecorder {
+    fn create(
+        path: &Path,
+        registry: &SpeciesRegistry,
+        world: &scenario::WorldConfig,
+        tick_secs: f32,
+    ) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&tick_secs.to_le_bytes())?;
+        let bounds = world.bounds;
+        for edge in [bounds.x0, bounds.x1, bounds.y0, bounds.y1] {
+            out.write_all(&edge.to_le_bytes())?;
+        }
+        let wraps = matches!(world.boundary, scenario::Boundary::Wrap);
+        out.write_all(&[wraps as u8])?;
+        out.write_all(&(registry.iter().count() as u32).to_le_bytes())?;
+        for (_, species) in registry.iter() {
+            out.write_all(&(species.name.len() as u32).to_le_bytes())?;
+            out.write_all(species.name.as_bytes())?;
+        }
+
+        Ok(ReplayRecorder {
+            out,
+            path: path.to_path_buf(),
+            ids: EntityHashMap::default(),
+            next_id: 0,
+        })
+    }
+
+    fn write_frame(
+        &mut self,
+        tick: u64,
+        entities: &[(Entity, ReplayKind, f32, Pose)],
+    ) -> io::Result<()> {
+        let alive: EntityHashMap<()> = entities.iter().map(|(entity, ..)| (*entity, ())).collect();
+        let mut despawned: Vec<u32> = self
+            .ids
+            .iter()
+            .filter(|(entity, _)| !alive.contains_key(*entity))
+            .map(|(_, id)| *id)
+            .collect();
+        despawned.sort_unstable();
+        self.ids.retain(|entity, _| alive.contains_key(entity));
+
+        let spawned: Vec<_> = entities
+            .iter()
+            .filter(|(entity, ..)| !self.ids.contains_key(entity))
+            .collect();
+
+        self.out.write_all(&tick.to_le_bytes())?;
+
+        self.out
+            .write_all(&(despawned.len() as u32).to_le_bytes())?;
+        for id in despawned {
+            self.out.write_all(&id.to_le_bytes())?;
+        }
+
+        self.out.write_all(&(spawned.len() as u32).to_le_bytes())?;
+        for (entity, kind, scale, pose) in spawned {
+            let id = self.next_id;
+            self.next_id += 1;
+            self.ids.insert(*entity, id);
+
+            let (tag, species) = match kind {
+                ReplayKind::Unit(species) => (0u8, species.0),
+                ReplayKind::Berry => (1, 0),
+                ReplayKind::Corpse(species) => (2, species.0),
+                ReplayKind::Bush => (3, 0),
+            };
+            self.out.write_all(&id.to_le_bytes())?;
+            self.out.write_all(&[tag])?;
+            self.out.write_all(&(species as u32).to_le_bytes())?;
+            self.out.write_all(&scale.to_le_bytes())?;
+            write_pose(&mut self.out, pose)?;
+        }
+
+        let units: Vec<_> = entities
+            .iter()
+            .filter(|(_, kind, ..)| matches!(kind, ReplayKind::Unit(_)))
+            .collect();
+        self.out.write_all(&(units.len() as u32).to_le_bytes())?;
+        for (entity, _, _, pose) in units {
+            self.out.write_all(&self.ids[entity].to_le_bytes())?;
+            write_pose(&mut self.out, pose)?;
+        }
+
+        self.out.flush()
+    }
+}
+
+fn write_pose(out: &mut impl Write, pose: &Pose) -> io::Result<()> {
+    out.write_all(&pose.position.x.to_le_bytes())?;
+    out.write_all(&pose.position.y.to_le_bytes())?;
+    out.write_all(&pose.rotation.to_le_bytes())
+}
+
+impl Replay {
+    /// Read a recorded run. Its species are matched to the registry by name.
+    pub fn load(path: &Path, registry: &SpeciesRegistry) -> Result<Self, ReplayError> {
+        let file = File::open(path).map_err(|err| ReplayError::Io(path.to_path_buf(), err))?;
+        let mut reader = ReplayReader(BufReader::new(file));
+        let invalid = |reason: &str| ReplayError::Invalid(path.to_path_buf(), reason.to_string());
+        let io_err = |err: io::Error| match err.kind() {
+            io::ErrorKind::UnexpectedEof => invalid("file is truncated"),
+            _ => ReplayError::Io(path.to_path_buf(), err),
+        };
+
+        let mut magic = [0; 4];
+        reader.0.read_exact(&mut magic).map_err(io_err)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a replay file"));
+        }
+        if reader.u32().map_err(io_err)? != VERSION {
+            return Err(invalid("unsupported replay version"));
+        }
+        let tick_secs = reader.f32().map_err(io_err)?;
+        let bounds = scenario::Bounds {
+            x0: reader.f32().map_err(io_err)?,
+            x1: reader.f32().map_err(io_err)?,
+            y0: reader.f32().map_err(io_err)?,
+            y1: reader.f32().map_err(io_err)?,
+        };
+        if !(bounds.x0 < bounds.x1 && bounds.y0 < bounds.y1) {
+            return Err(invalid("world bounds are empty"));
+        }
+        let wraps = reader.u8().map_err(io_err)? != 0;
+
+        let mut species = Vec::new();
+        for _ in 0..reader.u32().map_err(io_err)? {
+            let mut name = vec![0; reader.u32().map_err(io_err)? as usize];
+            reader.0.read_exact(&mut name).map_err(io_err)?;
+            let name = String::from_utf8_lossy(&name);
+            let id = registry
+                .id(&name)
+                .ok_or_else(|| invalid(&format!("unknown species '{name}'")))?;
+            species.push(id);
+        }
+
+        let mut frames = Vec::new();
+        let mut entities: Vec<ReplayEntity> = Vec::new();
+        loop {
+            let frame = frames.len();
+            let known = entities.len();
+            match reader.frame(frame, &mut entities, &species, path) {
+                Ok(Some(replay_frame)) => frames.push(replay_frame),
+                Ok(None) => break,
+                // A run that was killed while writing leaves the last frame
+                // unfinished. Everything before it is still good.
+                Err(ReplayError::Io(_, err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    warn!("{}: dropping unfinished last frame", path.display());
+                    entities.truncate(known);
+                    for entity in &mut entities {
+                        if entity.despawned == Some(frame) {
+                            entity.despawned = None;
+                        }
+                    }
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        if frames.is_empty() {
+            return Err(invalid("replay has no frames"));
+        }
+
+        Ok(Replay {
+            tick_secs,
+            bounds,
+            wraps,
+            frames,
+            entities,
+        })
+    }
+}
+
+struct ReplayReader(BufReader<File>);
+
+impl ReplayReader {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.0.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.bytes()?))
+    }
+
+    fn pose(&mut self) -> io::Result<Pose> {
+        Ok(Pose {
+            position: Vec2::new(self.f32()?, self.f32()?),
+            rotation: self.f32()?,
+        })
+    }
+
+    /// Read the next frame, whose index is `frame`, adding the entities it
+    /// spawns to `entities`. Returns `None` at the end of the file.
+    fn frame(
+        &mut self,
+        frame: usize,
+        entities: &mut Vec<ReplayEntity>,
+        species: &[species::SpeciesId],
+        path: &Path,
+    ) -> Result<Option<ReplayFrame>, ReplayError> {
+        let invalid = |reason: &str| ReplayError::Invalid(path.to_path_buf(), reason.to_string());
+        let io_err = |err: io::Error| ReplayError::Io(path.to_path_buf(), err);
+        let species_id = |index: u32| {
+            species
+                .get(index as usize)
+                .copied()
+                .ok_or_else(|| invalid("species index out of range"))
+        };
+
+        let Some(tick) = self.tick().map_err(io_err)? else {
+            return Ok(None);
+        };
+
+        for _ in 0..self.u32().map_err(io_err)? {
+            let id = self.u32().map_err(io_err)? as usize;
+            let entity = entities
+                .get_mut(id)
+                .ok_or_else(|| invalid("despawn of unknown entity"))?;
+            entity.despawned = Some(frame);
+        }
+
+        for _ in 0..self.u32().map_err(io_err)? {
+            let id = self.u32().map_err(io_err)? as usize;
+            if id != entities.len() {
+                return Err(invalid("entity ids out of order"));
+            }
+            let tag = self.u8().map_err(io_err)?;
+            let species = self.u32().map_err(io_err)?;
+            let kind = match tag {
+                0 => ReplayKind::Unit(species_id(species)?),
+                1 => ReplayKind::Berry,
+                2 => ReplayKind::Corpse(species_id(species)?),
+                3 => ReplayKind::Bush,
+                _ => return Err(invalid("unknown entity kind")),
+            };
+            let scale = self.f32().map_err(io_err)?;
+            let pose = self.pose().map_err(io_err)?;
+
+            entities.push(ReplayEntity {
+                kind,
+                scale,
+                pose,
+                spawned: frame,
+                despawned: None,
+            });
+        }
+
+        let mut units = Vec::new();
+        for _ in 0..self.u32().map_err(io_err)? {
+            let id = self.u32().map_err(io_err)?;
+            if id as usize >= entities.len() {
+                return Err(invalid("position of unknown entity"));
+            }
+            units.push((id, self.pose().map_err(io_err)?));
+        }
+
+        Ok(Some(ReplayFrame { tick, units }))
+    }
+
+    /// Tick of the next frame, or `None` at the end of the file.
+    fn tick(&mut self) -> io::Result<Option<u64>> {
+        let mut bytes = [0; 8];
+        match self.0.read(&mut bytes[..1])? {
+            0 => Ok(None),
+            _ => {
+                self.0.read_exact(&mut bytes[1..])?;
+                Ok(Some(u64::from_le_bytes(bytes)))
+            }
+        }
+    }
+}
+
+pub fn start_recording(
+    mut commands: Commands,
+    file: Res<ReplayFile>,
+    registry: Res<SpeciesRegistry>,
+    scenario: Res<Scenario>,
+    time: Res<Time<Fixed>>,
+) {
+    let tick_secs = time.timestep().as_secs_f32();
+    match ReplayRecorder::create(&file.0, &registry, &scenario.world, tick_secs) {
+        Ok(recorder) => commands.insert_resource(recorder),
+        Err(err) => error!("Failed to record to {}: {err}", file.0.display()),
+    }
+}
+
+pub fn record_frame(
+    mut commands: Commands,
+    mut recorder: ResMut<ReplayRecorder>,
+    game_data: Res<SimData>,
+    unit_query: Query<(Entity, &Transform, &unit::UnitType, &motion::Rotation)>,
+    berry_query: Query<(Entity, &Transform), With<berry::Berry>>,
+    corpse_query: Query<(Entity, &Transform, &unit::Corpse)>,
+    bush_query: Query<(Entity, &Transform), With<berry::Bush>>,
+) {
+    if !game_data.tick.is_multiple_of(REPLAY_INTERVAL) {
+        return;
+    }
+
+    let pose = |transform: &Transform, rotation: f32| Pose {
+        position: transform.translation.truncate(),
+        rotation,
+    };
+    let units = unit_query
+        .iter()
+        .map(|(entity, transform, unit, rotation)| {
+            let kind = ReplayKind::Unit(unit.0);
+            (entity, kind, transform.scale.x, pose(transform, rotation.0))
+        });
+    let berries = berry_query.iter().map(|(entity, transform)| {
+        (
+            entity,
+            ReplayKind::Berry,
+            transform.scale.x,
+            pose(transform, 0.0),
+        )
+    });
+    let corpses = corpse_query.iter().map(|(entity, transform, corpse)| {
+        let kind = ReplayKind::Corpse(corpse.unit.0);
+        let rotation = transform.rotation.to_euler(EulerRot::ZYX).0;
+        (entity, kind, transform.scale.x, pose(transform, rotation))
+    });
+    let bushes = bush_query.iter().map(|(entity, transform)| {
+        (
+            entity,
+            ReplayKind::Bush,
+            transform.scale.x,
+            pose(transform, 0.0),
+        )
+    });
+    let entities: Vec<_> = units.chain(berries).chain(corpses).chain(bushes).collect();
+
+    if let Err(err) = recorder.write_frame(game_data.tick, &entities) {
+        error!("Failed to record to {}: {err}", recorder.path.display());
+        commands.remove_resource::<ReplayRecorder>();
+    }
+}
+
+/// Make sure everything recorded so far is on disk when the app exits.
+pub fn flush_recording_on_app_exit(
+    mut app_exit: EventReader<AppExit>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    if let (Some(_), Some(mut recorder)) = (app_exit.read().next(), recorder) {
+        if let Err(err) = recorder.out.flush() {
+            error!("Failed to record to {}: {err}", recorder.path.display());
+        }
+    }
+}
+
+pub fn stop_recording(mut commands: Commands, recorder: Option<ResMut<ReplayRecorder>>) {
+    if let Some(mut recorder) = recorder {
+        if let Err(err) = recorder.out.flush() {
+            error!("Failed to record to {}: {err}", recorder.path.display());
+        }
+        commands.remove_resource::<ReplayRecorder>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn pose(x: f32) -> Pose {
+        Pose {
+            position: Vec2::new(x, 0.0),
+            rotation: 0.0,
+        }
+    }
+
+    #[test]
+    fn load_drops_unfinished_last_frame() {
+        let registry = SpeciesRegistry::load(&species::species_dir()).unwrap();
+        let (species, _) = registry.iter().next().unwrap();
+        let path = std::env::temp_dir().join(format!("replay-{}.bin", std::process::id()));
+
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+        let unit = |entity, x| (entity, ReplayKind::Unit(species), 1.0, pose(x));
+        let world = Scenario::default().world;
+        let mut recorder = ReplayRecorder::create(&path, &registry, &world, 0.5).unwrap();
+        recorder.write_frame(0, &[unit(a, 0.0)]).unwrap();
+        let first_frame = fs::metadata(&path).unwrap().len();
+        recorder.write_frame(8, &[unit(a, 1.0)]).unwrap();
+        let second_frame = fs::metadata(&path).unwrap().len();
+        // Despawns `a` and spawns `b`.
+        recorder.write_frame(16, &[unit(b, 2.0)]).unwrap();
+        drop(recorder);
+        let bytes = fs::read(&path).unwrap();
+
+        let replay = Replay::load(&path, &registry).unwrap();
+        assert_eq!(replay.bounds.size(), world.bounds.size());
+        assert!(!replay.wraps);
+        assert_eq!(replay.frames.len(), 3);
+        assert_eq!(replay.entities.len(), 2);
+        assert_eq!(replay.entities[0].despawned, Some(2));
+
+        // Cut the file anywhere within the last frame.
+        for len in second_frame + 1..bytes.len() as u64 {
+            fs::write(&path, &bytes[..len as usize]).unwrap();
+            let replay = Replay::load(&path, &registry).unwrap();
+
+            assert_eq!(replay.frames.len(), 2, "cut at {len}");
+            assert_eq!(replay.frames[1].tick, 8);
+            assert_eq!(replay.entities.len(), 1, "cut at {len}");
+            assert_eq!(replay.entities[0].despawned, None, "cut at {len}");
+        }
+
+        // Without a single whole frame there is nothing to play.
+        fs::write(&path, &bytes[..first_frame as usize - 1]).unwrap();
+        assert!(Replay::load(&path, &registry).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
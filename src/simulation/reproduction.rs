@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::*;
+
+/// Progress of a unit towards its next reproduction.
+#[derive(Component, Serialize, Deserialize, Clone, Default)]
+pub struct Fertility {
+    /// Seconds the unit has continuously stayed above its species' fullness
+    /// threshold.
+    pub time_above_threshold: f32,
+    /// Seconds left until the unit may reproduce again.
+    pub cooldown: f32,
+}
+
+/// Let units that have stayed well fed for long enough spend fullness on
+/// offspring spawned around them.
+#[allow(clippy::too_many_arguments)]
+pub fn reproduce(
+    mut commands: Commands,
+    mut query: Query<(
+        &Transform,
+        &unit::UnitType,
+        &genome::Genome,
+        &brain::Brain,
+        &senses::Senses,
+        &unit::Lineage,
+        &mut hunger::Hunger,
+        &mut Fertility,
+    )>,
+    registry: Res<SpeciesRegistry>,
+    scenario: Res<Scenario>,
+    mut rng: ResMut<rng::SimRng>,
+    mut births: EventWriter<unit::BirthEvent>,
+    mut game_data: ResMut<SimData>,
+    time: Res<Time<Fixed>>,
+) {
+    let dt = time.delta_secs();
+
+    for (transform, unit, genome, brain, senses, lineage, mut hunger, mut fertility) in
+        query.iter_mut()
+    {
+        let species = registry.get(unit.0);
+        let Some(reproduction) = species.reproduction else {
+            continue;
+        };
+
+        fertility.cooldown = (fertility.cooldown - dt).max(0.0);
+        if hunger.curr_fullness >= reproduction.fullness_threshold * hunger.max_fullness {
+            fertility.time_above_threshold += dt;
+        } else {
+            fertility.time_above_threshold = 0.0;
+        }
+
+        if fertility.cooldown > 0.0
+            || fertility.time_above_threshold < reproduction.time_above_threshold
+            || hunger.curr_fullness <= reproduction.cost
+        {
+            continue;
+        }
+
+        hunger.curr_fullness -= reproduction.cost;
+        fertility.time_above_threshold = 0.0;
+        fertility.cooldown = reproduction.cooldown;
+
+        let mutation = scenario
+            .species
+            .get(&species.name)
+            .map_or(species.mutation, |config| config.mutation(species));
+        let offspring_fullness = reproduction.cost / reproduction.litter_size as f32;
+        for _ in 0..reproduction.litter_size {
+            let position = berry::position_near(
+                transform.translation.truncate(),
+                reproduction.spawn_radius,
+                &scenario.world,
+                &mut rng,
+            );
+
+            let offspring_genome = match &mutation {
+                Some(mutation) => genome.mutated(mutation, &mut rng.0),
+                None => genome.clone(),
+            };
+
+            let offspring_senses = senses::Senses {
+                vision_radius: offspring_genome.sensor_range,
+                ..senses.clone()
+            };
+
+            unit::spawn_unit(
+                &mut commands,
+                &registry,
+                unit::UnitSpawn {
+                    species: unit.0,
+                    position,
+                    genome: offspring_genome,
+                    fullness: offspring_fullness,
+                    brain: brain.clone(),
+                    senses: offspring_senses,
+                    lineage: game_data.new_lineage(Some(lineage)),
+                },
+            );
+            births.write(unit::BirthEvent { unit: *unit });
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// Seed given on the command line. Used to initialize `SimRng` every time a
+/// simulation starts, taking precedence over the seed in the `Scenario`.
+#[derive(Resource, Clone, Copy)]
+pub struct SimSeed(pub u64);
+
+/// The single source of randomness for the simulation. Every system that
+/// makes a random decision must draw from this so that a run is fully
+/// determined by its seed.
+#[derive(Resource)]
+pub struct SimRng(pub ChaCha8Rng);
+
+/// Everything needed to continue the random sequence of a `SimRng` exactly
+/// where it left off.
+#[derive(Serialize, Deserialize)]
+pub struct RngState {
+    seed: [u8; 32],
+    stream: u64,
+    /// Low and high halves of the 128-bit position in the stream, since RON
+    /// has no 128-bit integers.
+    word_pos: [u64; 2],
+}
+
+impl SimRng {
+    pub fn from_seed(seed: SimSeed) -> Self {
+        SimRng(ChaCha8Rng::seed_from_u64(seed.0))
+    }
+
+    pub fn state(&self) -> RngState {
+        let word_pos = self.0.get_word_pos();
+        RngState {
+            seed: self.0.get_seed(),
+            stream: self.0.get_stream(),
+            word_pos: [word_pos as u64, (word_pos >> 64) as u64],
+        }
+    }
+
+    pub fn from_state(state: &RngState) -> Self {
+        let mut rng = ChaCha8Rng::from_seed(state.seed);
+        rng.set_stream(state.stream);
+        rng.set_word_pos(u128::from(state.word_pos[0]) | u128::from(state.word_pos[1]) << 64);
+        SimRng(rng)
+    }
+}
+
+impl SimSeed {
+    /// Pick a seed from OS entropy. It is logged so that the run can be
+    /// reproduced afterwards.
+    pub fn random() -> Self {
+        let seed = rand::random();
+        info!("No seed given, using seed {seed}");
+        SimSeed(seed)
+    }
+}
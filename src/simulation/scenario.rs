@@ -0,0 +1,630 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{asset::io::file::FileAssetReader, prelude::*};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{
+    berry::Bush,
+    brain::{Brain, BrainConfig},
+    constants::*,
+    genome::{Genome, Mutation},
+    obstacle::Obstacle,
+    rng::SimRng,
+    senses::Senses,
+    species::{Species, SpeciesRegistry},
+    terrain::{Biome, BiomeEffects, Terrain},
+};
+
+/// Initial populations and world parameters of a simulation run.
+#[derive(Resource, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Seed for `SimRng`. A seed given on the command line takes precedence.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub world: WorldConfig,
+    /// Initial population of each species, keyed by species name.
+    pub species: BTreeMap<String, SpeciesConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WorldConfig {
+    pub bounds: Bounds,
+    /// Berries kept at random positions, topped up as soon as one is eaten.
+    #[serde(default)]
+    pub max_berries: u64,
+    /// Patches of bushes that berries grow back on.
+    #[serde(default)]
+    pub patches: Vec<PatchConfig>,
+    #[serde(default)]
+    pub boundary: Boundary,
+    #[serde(default)]
+    pub collisions: CollisionConfig,
+    /// Map of the biomes in the world. Without one the world is all meadow.
+    #[serde(default)]
+    pub terrain: Option<TerrainConfig>,
+    /// Rocks, trees and walls that units cannot pass through.
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+}
+
+/// Where the biomes of the world are and how they behave.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TerrainConfig {
+    /// PNG image, relative to the assets directory, that is stretched over the
+    /// bounds.
+    pub map: String,
+    /// Color of each biome in the map. Pixels are of the biome with the
+    /// closest color, so grayscale maps work with a palette of grays.
+    #[serde(default)]
+    pub palette: Option<Vec<(Biome, (u8, u8, u8))>>,
+    /// Effects of the biomes that differ from the defaults.
+    #[serde(default)]
+    pub effects: BTreeMap<Biome, BiomeEffects>,
+}
+
+/// A clump of berry bushes. Parameters of the bushes that are left out are
+/// taken from the defaults.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PatchConfig {
+    pub center: (f32, f32),
+    /// Maximum distance from the center that bushes are placed at.
+    pub radius: f32,
+    pub bushes: usize,
+    /// Berries per second that a bush grows back.
+    #[serde(default)]
+    pub regrowth_rate: Option<f32>,
+    /// Berries that a bush carries when full.
+    #[serde(default)]
+    pub capacity: Option<u32>,
+    /// Fullness gained by eating one of the berries.
+    #[serde(default)]
+    pub berry_yield: Option<f32>,
+}
+
+/// How units respond to their bodies overlapping.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct CollisionConfig {
+    /// Share of the speed at which two bodies approach each other that they
+    /// separate with.
+    pub restitution: f32,
+    /// Share of the overlap between two bodies that is removed every tick by
+    /// moving them apart. Zero leaves it to the impulses alone.
+    pub correction: f32,
+}
+
+/// What happens to units that reach the edge of the `Bounds`.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub enum Boundary {
+    /// Solid walls that units bounce off, keeping `restitution` of their speed
+    /// towards the wall.
+    Wall { restitution: f32 },
+    /// Units within `margin` of an edge are pushed back in, with an
+    /// acceleration that grows to `strength` at the edge and keeps growing
+    /// beyond it.
+    Soft { margin: f32, strength: f32 },
+    /// Units leaving on one side come back in on the opposite side.
+    Wrap,
+}
+
+/// Axis aligned rectangle that units and berries are spawned within.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Bounds {
+    pub x0: f32,
+    pub x1: f32,
+    pub y0: f32,
+    pub y1: f32,
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        Boundary::Wall {
+            restitution: BOUNDARY_RESTITUTION,
+        }
+    }
+}
+
+impl Default for CollisionConfig {
+    fn default() -> Self {
+        CollisionConfig {
+            restitution: COLLISION_RESTITUTION,
+            correction: COLLISION_CORRECTION,
+        }
+    }
+}
+
+impl Bounds {
+    pub fn min(&self) -> Vec2 {
+        Vec2::new(self.x0, self.y0)
+    }
+
+    pub fn max(&self) -> Vec2 {
+        Vec2::new(self.x1, self.y1)
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max() - self.min()
+    }
+}
+
+impl PatchConfig {
+    pub fn center(&self) -> Vec2 {
+        Vec2::new(self.center.0, self.center.1)
+    }
+
+    /// A bush of this patch, before it has grown any berries.
+    pub fn bush(&self) -> Bush {
+        Bush {
+            regrowth_rate: self.regrowth_rate.unwrap_or(BUSH_REGROWTH_RATE),
+            capacity: self.capacity.unwrap_or(BUSH_CAPACITY),
+            berry_yield: self.berry_yield.unwrap_or(BERRY_FULLNESS_GAIN),
+            berries: 0,
+            growth: 0.0,
+        }
+    }
+}
+
+impl TerrainConfig {
+    pub fn palette(&self) -> Vec<(Biome, [u8; 3])> {
+        match &self.palette {
+            Some(palette) => palette
+                .iter()
+                .map(|(biome, (r, g, b))| (*biome, [*r, *g, *b]))
+                .collect(),
+            None => Biome::ALL
+                .into_iter()
+                .map(|biome| (biome, biome.color()))
+                .collect(),
+        }
+    }
+
+    pub fn effects(&self, biome: Biome) -> BiomeEffects {
+        self.effects
+            .get(&biome)
+            .copied()
+            .unwrap_or_else(|| biome.effects())
+    }
+}
+
+impl WorldConfig {
+    /// Bring `position` within the bounds, across the edges of a wrapping
+    /// world and to the nearest edge otherwise.
+    pub fn contain(&self, position: Vec2) -> Vec2 {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        match self.boundary {
+            Boundary::Wrap => min + (position - min).rem_euclid(self.bounds.size()),
+            _ => position.clamp(min, max),
+        }
+    }
+
+    /// Shortest offset from `from` to `to`, which crosses the edges of a
+    /// wrapping world if that is shorter.
+    pub fn offset(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let offset = to - from;
+        match self.boundary {
+            Boundary::Wrap => {
+                let size = self.bounds.size();
+                offset - size * (offset / size).round()
+            }
+            _ => offset,
+        }
+    }
+
+    /// Shifts of `center` at which a query within `radius` also finds what
+    /// lies across the edges of a wrapping world. Positions found around a
+    /// shifted center have to be shifted back by the same amount. Zero is
+    /// always included.
+    pub fn shifts(&self, center: Vec2, radius: f32) -> impl Iterator<Item = Vec2> {
+        let wrap = matches!(self.boundary, Boundary::Wrap);
+        let (min, max, size) = (self.bounds.min(), self.bounds.max(), self.bounds.size());
+
+        [-1.0, 0.0, 1.0]
+            .into_iter()
+            .flat_map(|y| [-1.0, 0.0, 1.0].map(|x| Vec2::new(x, y)))
+            .map(move |shift| shift * size)
+            .filter(move |shift| {
+                let shifted = center + *shift;
+                *shift == Vec2::ZERO || (wrap && shifted.clamp(min, max).distance(shifted) < radius)
+            })
+    }
+}
+
+/// Initial population of a species. Parameters that are left out are taken
+/// from the species definition.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SpeciesConfig {
+    pub count: usize,
+    #[serde(default)]
+    pub max_speed: Option<f32>,
+    #[serde(default)]
+    pub max_acceleration: Option<f32>,
+    #[serde(default)]
+    pub max_fullness: Option<f32>,
+    /// Fullness of the initial population. Defaults to the maximum fullness.
+    #[serde(default)]
+    pub initial_fullness: Option<f32>,
+    #[serde(default)]
+    pub drain_per_unit_traveled: Option<f32>,
+    #[serde(default)]
+    pub body_scale: Option<f32>,
+    #[serde(default)]
+    pub sensor_range: Option<f32>,
+    /// Full angle of the view cone in degrees.
+    #[serde(default)]
+    pub field_of_view: Option<f32>,
+    #[serde(default)]
+    pub line_of_sight: Option<bool>,
+    #[serde(default)]
+    pub mutation: Option<Mutation>,
+    /// Brains to give the initial population, each paired with the weight of
+    /// the share of units that get it. Defaults to the species' brain.
+    #[serde(default)]
+    pub brains: Option<Vec<(f32, BrainConfig)>>,
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Invalid { field: String, reason: String },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            ScenarioError::Parse(path, err) => write!(f, "{}:{err}", path.display()),
+            ScenarioError::Invalid { field, reason } => write!(f, "invalid '{field}': {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            seed: None,
+            world: WorldConfig {
+                bounds: Bounds {
+                    x0: PLAYABLE_AREA_X0,
+                    x1: PLAYABLE_AREA_X1,
+                    y0: PLAYABLE_AREA_Y0,
+                    y1: PLAYABLE_AREA_Y1,
+                },
+                max_berries: 0,
+                // One patch in the middle of each quarter of the area.
+                patches: [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+                    .map(|(x, y)| PatchConfig {
+                        center: (x * PLAYABLE_AREA_X1 / 2.0, y * PLAYABLE_AREA_Y1 / 2.0),
+                        radius: PATCH_RADIUS,
+                        bushes: BUSHES_PER_PATCH,
+                        regrowth_rate: None,
+                        capacity: None,
+                        berry_yield: None,
+                    })
+                    .to_vec(),
+                boundary: Boundary::default(),
+                collisions: CollisionConfig::default(),
+                terrain: None,
+                obstacles: Vec::new(),
+            },
+            species: BTreeMap::from([
+                (
+                    "fernworm".to_string(),
+                    SpeciesConfig {
+                        count: NUM_FERNWORMS,
+                        ..default()
+                    },
+                ),
+                (
+                    "zyrthid".to_string(),
+                    SpeciesConfig {
+                        count: NUM_ZYRTHIDS,
+                        ..default()
+                    },
+                ),
+            ]),
+        }
+    }
+}
+
+impl Scenario {
+    /// Read, parse and validate a scenario file.
+    pub fn load(path: &Path, registry: &SpeciesRegistry) -> Result<Self, ScenarioError> {
+        let text =
+            fs::read_to_string(path).map_err(|err| ScenarioError::Io(path.to_path_buf(), err))?;
+        let scenario: Scenario =
+            ron::from_str(&text).map_err(|err| ScenarioError::Parse(path.to_path_buf(), err))?;
+        scenario.validate(registry)?;
+
+        Ok(scenario)
+    }
+
+    pub fn validate(&self, registry: &SpeciesRegistry) -> Result<(), ScenarioError> {
+        let bounds = &self.world.bounds;
+        for (field, value) in [
+            ("world.bounds.x0", bounds.x0),
+            ("world.bounds.x1", bounds.x1),
+            ("world.bounds.y0", bounds.y0),
+            ("world.bounds.y1", bounds.y1),
+        ] {
+            check(field, value.is_finite(), "must be a finite number")?;
+        }
+        check(
+            "world.bounds.x1",
+            bounds.x0 < bounds.x1,
+            "must be greater than x0",
+        )?;
+        check(
+            "world.bounds.y1",
+            bounds.y0 < bounds.y1,
+            "must be greater than y0",
+        )?;
+
+        match self.world.boundary {
+            Boundary::Wall { restitution } => check(
+                "world.boundary.restitution",
+                (0.0..=1.0).contains(&restitution),
+                "must be between 0 and 1",
+            )?,
+            Boundary::Soft { margin, strength } => {
+                check(
+                    "world.boundary.margin",
+                    margin.is_finite() && margin > 0.0,
+                    "must be a positive number",
+                )?;
+                check(
+                    "world.boundary.strength",
+                    strength.is_finite() && strength >= 0.0,
+                    "must be a non-negative number",
+                )?;
+            }
+            Boundary::Wrap => {}
+        }
+        for (i, patch) in self.world.patches.iter().enumerate() {
+            let field = |name: &str| format!("world.patches[{i}].{name}");
+            check(
+                &field("center"),
+                patch.center().is_finite(),
+                "must be finite numbers",
+            )?;
+            check(
+                &field("radius"),
+                patch.radius.is_finite() && patch.radius >= 0.0,
+                "must be a non-negative number",
+            )?;
+            let bush = patch.bush();
+            for (name, value) in [
+                ("regrowth_rate", bush.regrowth_rate),
+                ("berry_yield", bush.berry_yield),
+            ] {
+                check(
+                    &field(name),
+                    value.is_finite() && value >= 0.0,
+                    "must be a non-negative number",
+                )?;
+            }
+        }
+
+        for (i, obstacle) in self.world.obstacles.iter().enumerate() {
+            if let Some((field, reason)) = obstacle.invalid_field() {
+                check(&format!("world.obstacles[{i}].{field}"), false, reason)?;
+            }
+        }
+        if let Some(terrain) = &self.world.terrain {
+            check(
+                "world.terrain.palette",
+                terrain
+                    .palette
+                    .as_ref()
+                    .is_none_or(|palette| !palette.is_empty()),
+                "must not be empty",
+            )?;
+            for (biome, effects) in &terrain.effects {
+                if let Some(field) = effects.invalid_field() {
+                    check(
+                        &format!("world.terrain.effects.{biome:?}.{field}"),
+                        false,
+                        "must be a non-negative number",
+                    )?;
+                }
+            }
+            Terrain::load(terrain, self.world.bounds)?;
+        }
+
+        let collisions = &self.world.collisions;
+        for (field, value) in [
+            ("world.collisions.restitution", collisions.restitution),
+            ("world.collisions.correction", collisions.correction),
+        ] {
+            check(
+                field,
+                (0.0..=1.0).contains(&value),
+                "must be between 0 and 1",
+            )?;
+        }
+
+        for (name, config) in &self.species {
+            let Some(id) = registry.id(name) else {
+                return Err(ScenarioError::Invalid {
+                    field: format!("species.{name}"),
+                    reason: "unknown species".to_string(),
+                });
+            };
+            config.validate(name, registry.get(id))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SpeciesConfig {
+    /// Genome of the initial population.
+    pub fn genome(&self, species: &Species) -> Genome {
+        let default = species.default_genome();
+        Genome {
+            max_speed: self.max_speed.unwrap_or(default.max_speed),
+            max_acceleration: self.max_acceleration.unwrap_or(default.max_acceleration),
+            max_fullness: self.max_fullness.unwrap_or(default.max_fullness),
+            drain_per_unit_traveled: self
+                .drain_per_unit_traveled
+                .unwrap_or(default.drain_per_unit_traveled),
+            body_scale: self.body_scale.unwrap_or(default.body_scale),
+            sensor_range: self.sensor_range.unwrap_or(default.sensor_range),
+            brain_weights: default.brain_weights,
+        }
+    }
+
+    /// Senses of a unit of the initial population with `genome`.
+    pub fn senses(&self, species: &Species, genome: &Genome) -> Senses {
+        let default = species.senses(genome);
+        Senses {
+            field_of_view: self
+                .field_of_view
+                .map_or(default.field_of_view, f32::to_radians),
+            line_of_sight: self.line_of_sight.unwrap_or(default.line_of_sight),
+            ..default
+        }
+    }
+
+    pub fn initial_fullness(&self, species: &Species) -> f32 {
+        self.initial_fullness
+            .unwrap_or_else(|| self.genome(species).max_fullness)
+    }
+
+    /// Pick a brain for a unit of the initial population.
+    pub fn brain(&self, species: &Species, rng: &mut SimRng) -> Brain {
+        let Some(brains) = &self.brains else {
+            return species.brain.build(rng);
+        };
+
+        let total: f32 = brains.iter().map(|(weight, _)| weight).sum();
+        let mut pick = rng.0.gen_range(0.0..total);
+        for (weight, brain) in brains {
+            if pick < *weight {
+                return brain.build(rng);
+            }
+            pick -= weight;
+        }
+
+        // Only reachable through rounding errors.
+        brains[brains.len() - 1].1.build(rng)
+    }
+
+    /// Mutation applied to offspring of this species.
+    pub fn mutation(&self, species: &Species) -> Option<Mutation> {
+        self.mutation.or(species.mutation)
+    }
+
+    fn validate(&self, name: &str, species: &Species) -> Result<(), ScenarioError> {
+        for (field, value) in [
+            ("max_speed", self.max_speed),
+            ("max_acceleration", self.max_acceleration),
+            ("max_fullness", self.max_fullness),
+            ("initial_fullness", self.initial_fullness),
+            ("body_scale", self.body_scale),
+            ("sensor_range", self.sensor_range),
+        ] {
+            if let Some(value) = value {
+                check(
+                    &format!("species.{name}.{field}"),
+                    value.is_finite() && value > 0.0,
+                    "must be a positive number",
+                )?;
+            }
+        }
+        if let Some(field_of_view) = self.field_of_view {
+            check(
+                &format!("species.{name}.field_of_view"),
+                field_of_view > 0.0 && field_of_view <= 360.0,
+                "must be greater than 0 and at most 360",
+            )?;
+        }
+        if let Some(drain) = self.drain_per_unit_traveled {
+            check(
+                &format!("species.{name}.drain_per_unit_traveled"),
+                drain.is_finite() && drain >= 0.0,
+                "must be a non-negative number",
+            )?;
+        }
+        check(
+            &format!("species.{name}.initial_fullness"),
+            self.initial_fullness(species) <= self.genome(species).max_fullness,
+            "must be at most max_fullness",
+        )?;
+        if let Some(field) = self.mutation.as_ref().and_then(Mutation::invalid_field) {
+            check(
+                &format!("species.{name}.mutation.{field}"),
+                false,
+                "out of range",
+            )?;
+        }
+        if let Some(brains) = &self.brains {
+            check(
+                &format!("species.{name}.brains"),
+                !brains.is_empty(),
+                "must not be empty",
+            )?;
+            for (i, (weight, brain)) in brains.iter().enumerate() {
+                check(
+                    &format!("species.{name}.brains[{i}].0"),
+                    weight.is_finite() && *weight > 0.0,
+                    "must be a positive number",
+                )?;
+                if let Some((field, reason)) = brain.invalid_field() {
+                    check(
+                        &format!("species.{name}.brains[{i}].1.{field}"),
+                        false,
+                        reason,
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Directory holding the scenario files that are selectable from the menu.
+pub fn scenario_dir() -> PathBuf {
+    FileAssetReader::get_base_path().join("assets/scenarios")
+}
+
+/// All scenario files in `scenario_dir`, sorted by name.
+pub fn list_scenarios() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(scenario_dir()) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .collect();
+    paths.sort();
+
+    paths
+}
+
+fn check(field: &str, ok: bool, reason: &str) -> Result<(), ScenarioError> {
+    if ok {
+        Ok(())
+    } else {
+        Err(ScenarioError::Invalid {
+            field: field.to_string(),
+            reason: reason.to_string(),
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::*;
+
+/// What a unit can perceive. Brains only react to what lies within the
+/// unit's view cone.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Senses {
+    /// Distance within which the unit sees. Taken from the genome's sensor
+    /// range when the unit is spawned.
+    pub vision_radius: f32,
+    /// Full angle of the view cone around the unit's heading, in radians.
+    pub field_of_view: f32,
+    /// Whether obstacles block the view.
+    pub line_of_sight: bool,
+}
+
+impl Senses {
+    /// Whether a unit at `position` that is turned by `rotation` sees what is
+    /// at `offset` from it.
+    pub fn sees(
+        &self,
+        world: &scenario::WorldConfig,
+        position: Vec2,
+        rotation: f32,
+        offset: Vec2,
+    ) -> bool {
+        if offset.length_squared() >= self.vision_radius * self.vision_radius {
+            return false;
+        }
+        if self.field_of_view < 2.0 * PI {
+            let heading = Vec2::new(-rotation.sin(), rotation.cos());
+            // Whatever is right on top of the unit is seen.
+            if offset != Vec2::ZERO && heading.angle_to(offset).abs() > self.field_of_view / 2.0 {
+                return false;
+            }
+        }
+
+        !self.line_of_sight || world.is_clear(position, offset)
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::*;
+
+/// Directory that snapshots saved with the hotkey are written to and that the
+/// menu lists snapshots from.
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// File that a snapshot is written to when the app exits.
+#[derive(Resource)]
+pub struct SnapshotFile(pub PathBuf);
+
+/// Request to save a snapshot to `SNAPSHOT_DIR`, named after the current tick.
+#[derive(Event)]
+pub struct SaveSnapshot;
+
+/// The complete state of a running simulation. While inserted as a resource,
+/// entering `AppState::Simulation` restores it instead of setting up the
+/// `Scenario`.
+#[derive(Resource, Serialize, Deserialize)]
+pub struct Snapshot {
+    scenario: Scenario,
+    tick: u64,
+    num_berries: u64,
+    max_berries: u64,
+    next_unit_id: u64,
+    rng: rng::RngState,
+    /// In query iteration order, so that the restored simulation processes
+    /// units in the same order as the saved one.
+    units: Vec<UnitState>,
+    bushes: Vec<BushState>,
+    berries: Vec<BerryState>,
+    corpses: Vec<CorpseState>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TransformState {
+    translation: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UnitState {
+    /// Species name rather than id, since ids depend on which species files
+    /// exist.
+    species: String,
+    transform: TransformState,
+    rotation: motion::Rotation,
+    moving_body: motion::MovingBody,
+    hunger: hunger::Hunger,
+    target_point: motion::TargetPoint,
+    fertility: reproduction::Fertility,
+    genome: genome::Genome,
+    brain: brain::Brain,
+    route: obstacle::Route,
+    senses: senses::Senses,
+    lineage: unit::Lineage,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BushState {
+    transform: TransformState,
+    bush: berry::Bush,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BerryState {
+    transform: TransformState,
+    fullness: f32,
+    /// Index into `Snapshot::bushes` of the bush the berry grew on.
+    bush: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CorpseState {
+    species: String,
+    transform: TransformState,
+    biomass: f32,
+    nutrients: f32,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Serialize(ron::Error),
+    Invalid { field: String, reason: String },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            SnapshotError::Parse(path, err) => write!(f, "{}:{err}", path.display()),
+            SnapshotError::Serialize(err) => write!(f, "{err}"),
+            SnapshotError::Invalid { field, reason } => write!(f, "invalid '{field}': {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<&Transform> for TransformState {
+    fn from(transform: &Transform) -> Self {
+        TransformState {
+            translation: transform.translation,
+            rotation: transform.rotation,
+            scale: transform.scale,
+        }
+    }
+}
+
+impl From<TransformState> for Transform {
+    fn from(state: TransformState) -> Self {
+        Transform {
+            translation: state.translation,
+            rotation: state.rotation,
+            scale: state.scale,
+        }
+    }
+}
+
+impl Snapshot {
+    /// Read, parse and validate a snapshot file.
+    pub fn load(path: &Path, registry: &SpeciesRegistry) -> Result<Self, SnapshotError> {
+        let text =
+            fs::read_to_string(path).map_err(|err| SnapshotError::Io(path.to_path_buf(), err))?;
+        let snapshot: Snapshot =
+            ron::from_str(&text).map_err(|err| SnapshotError::Parse(path.to_path_buf(), err))?;
+        snapshot.validate(registry)?;
+
+        Ok(snapshot)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let text = ron::to_string(self).map_err(SnapshotError::Serialize)?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|err| SnapshotError::Io(dir.to_path_buf(), err))?;
+        }
+        fs::write(path, text).map_err(|err| SnapshotError::Io(path.to_path_buf(), err))
+    }
+
+    fn validate(&self, registry: &SpeciesRegistry) -> Result<(), SnapshotError> {
+        self.scenario
+            .validate(registry)
+            .map_err(|err| SnapshotError::Invalid {
+                field: "scenario".to_string(),
+                reason: err.to_string(),
+            })?;
+
+        for (i, berry) in self.berries.iter().enumerate() {
+            if berry.bush.is_some_and(|bush| bush >= self.bushes.len()) {
+                return Err(SnapshotError::Invalid {
+                    field: format!("berries[{i}].bush"),
+                    reason: "no such bush".to_string(),
+                });
+            }
+        }
+
+        let species = self.units.iter().map(|unit| &unit.species);
+        let corpse_species = self.corpses.iter().map(|corpse| &corpse.species);
+        for name in species.chain(corpse_species) {
+            if registry.id(name).is_none() {
+                return Err(SnapshotError::Invalid {
+                    field: format!("species '{name}'"),
+                    reason: "unknown species".to_string(),
+                });
+            }
+        }
+
+        for (i, unit) in self.units.iter().enumerate() {
+            if let brain::Brain::Neural { hidden } = unit.brain {
+                let expected = brain::neural::weight_count(hidden);
+                if unit.genome.brain_weights.len() != expected {
+                    return Err(SnapshotError::Invalid {
+                        field: format!("units[{i}].genome.brain_weights"),
+                        reason: format!("must have {expected} weights for {hidden} hidden neurons"),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spawn everything in the snapshot and put back the simulation's
+    /// resources.
+    fn restore(self, world: &mut World) {
+        let registry = world.resource::<SpeciesRegistry>();
+        // Validated when the snapshot is loaded.
+        let id = |name: &str| registry.id(name).expect("snapshot has an unknown species");
+        let unit_ids: Vec<_> = self.units.iter().map(|unit| id(&unit.species)).collect();
+        let corpse_ids: Vec<_> = self
+            .corpses
+            .iter()
+            .map(|corpse| id(&corpse.species))
+            .collect();
+
+        for (unit, species) in self.units.into_iter().zip(unit_ids) {
+            world.spawn((
+                SimulationComponent,
+                unit::UnitBundle {
+                    unit: unit::UnitType(species),
+                    rotation: unit.rotation,
+                    transform: unit.transform.into(),
+                    moving_body: unit.moving_body,
+                    hunger: unit.hunger,
+                    target_point: unit.target_point,
+                    fertility: unit.fertility,
+                    genome: unit.genome,
+                    brain: unit.brain,
+                    target: brain::Target::default(),
+                    route: unit.route,
+                    senses: unit.senses,
+                    lineage: unit.lineage,
+                },
+            ));
+        }
+
+        let bushes: Vec<Entity> = self
+            .bushes
+            .into_iter()
+            .map(|bush| {
+                world
+                    .spawn((
+                        SimulationComponent,
+                        bush.bush,
+                        Transform::from(bush.transform),
+                    ))
+                    .id()
+            })
+            .collect();
+
+        for berry in self.berries {
+            world.spawn((
+                SimulationComponent,
+                berry::Berry {
+                    fullness: berry.fullness,
+                    bush: berry.bush.map(|bush| bushes[bush]),
+                },
+                Transform::from(berry.transform),
+            ));
+        }
+
+        for (corpse, species) in self.corpses.into_iter().zip(corpse_ids) {
+            world.spawn((
+                SimulationComponent,
+                unit::Corpse {
+                    unit: unit::UnitType(species),
+                    biomass: corpse.biomass,
+                    nutrients: corpse.nutrients,
+                },
+                Transform::from(corpse.transform),
+            ));
+        }
+
+        let mut game_data = world.resource_mut::<SimData>();
+        game_data.tick = self.tick;
+        game_data.num_berries = self.num_berries;
+        game_data.max_berries = self.max_berries;
+        game_data.next_unit_id = self.next_unit_id;
+        world.insert_resource(rng::SimRng::from_state(&self.rng));
+        world.insert_resource(self.scenario);
+    }
+}
+
+/// Restore the `Snapshot` resource, if there is one, and remove it so that the
+/// next simulation starts from its scenario again.
+pub fn restore_snapshot(world: &mut World) {
+    if let Some(snapshot) = world.remove_resource::<Snapshot>() {
+        snapshot.restore(world);
+    }
+}
+
+/// Capture the current state of the simulation.
+fn take_snapshot(world: &mut World) -> Snapshot {
+    let mut unit_query = world.query::<(
+        &unit::UnitType,
+        &Transform,
+        &motion::Rotation,
+        &motion::MovingBody,
+        &hunger::Hunger,
+        &motion::TargetPoint,
+        &reproduction::Fertility,
+        &genome::Genome,
+        &brain::Brain,
+        &obstacle::Route,
+        &senses::Senses,
+        &unit::Lineage,
+        Option<&presentation::Interpolated>,
+    )>();
+    let mut bush_query = world.query::<(Entity, &berry::Bush, &Transform)>();
+    let mut berry_query = world.query::<(&berry::Berry, &Transform)>();
+    let mut corpse_query = world.query::<(&unit::Corpse, &Transform)>();
+
+    let registry = world.resource::<SpeciesRegistry>();
+    let name = |unit: &unit::UnitType| registry.get(unit.0).name.clone();
+
+    let units = unit_query
+        .iter(world)
+        .map(
+            |(
+                unit,
+                transform,
+                rotation,
+                moving_body,
+                hunger,
+                target_point,
+                fertility,
+                genome,
+                brain,
+                route,
+                senses,
+                lineage,
+                interpolated,
+            )| {
+                // Outside of fixed updates, the transform of a unit that is
+                // drawn holds where it is shown rather than where it is.
+                let mut transform = TransformState::from(transform);
+                if let Some(interpolated) = interpolated {
+                    transform.translation = interpolated.current;
+                }
+
+                UnitState {
+                    species: name(unit),
+                    transform,
+                    rotation: rotation.clone(),
+                    moving_body: moving_body.clone(),
+                    hunger: hunger.clone(),
+                    target_point: target_point.clone(),
+                    fertility: fertility.clone(),
+                    genome: genome.clone(),
+                    brain: brain.clone(),
+                    route: route.clone(),
+                    senses: senses.clone(),
+                    lineage: lineage.clone(),
+                }
+            },
+        )
+        .collect();
+
+    let mut bush_indices = EntityHashMap::default();
+    let bushes = bush_query
+        .iter(world)
+        .enumerate()
+        .map(|(i, (entity, bush, transform))| {
+            bush_indices.insert(entity, i);
+            BushState {
+                transform: transform.into(),
+                bush: bush.clone(),
+            }
+        })
+        .collect();
+
+    let berries = berry_query
+        .iter(world)
+        .map(|(berry, transform)| BerryState {
+            transform: transform.into(),
+            fullness: berry.fullness,
+            bush: berry.bush.and_then(|bush| bush_indices.get(&bush).copied()),
+        })
+        .collect();
+
+    let corpses = corpse_query
+        .iter(world)
+        .map(|(corpse, transform)| CorpseState {
+            species: name(&corpse.unit),
+            transform: transform.into(),
+            biomass: corpse.biomass,
+            nutrients: corpse.nutrients,
+        })
+        .collect();
+
+    let game_data = world.resource::<SimData>();
+    Snapshot {
+        scenario: world.resource::<Scenario>().clone(),
+        tick: game_data.tick,
+        num_berries: game_data.num_berries,
+        max_berries: game_data.max_berries,
+        next_unit_id: game_data.next_unit_id,
+        rng: world.resource::<rng::SimRng>().state(),
+        units,
+        bushes,
+        berries,
+        corpses,
+    }
+}
+
+fn save(world: &mut World, path: &Path) {
+    match take_snapshot(world).save(path) {
+        Ok(()) => info!("Saved snapshot to {}", path.display()),
+        Err(err) => error!("Failed to save snapshot: {err}"),
+    }
+}
+
+pub fn save_snapshot_on_request(world: &mut World) {
+    let requested = world.resource_mut::<Events<SaveSnapshot>>().drain().count() > 0;
+    if requested {
+        let tick = world.resource::<SimData>().tick;
+        save(
+            world,
+            &Path::new(SNAPSHOT_DIR).join(format!("tick-{tick}.ron")),
+        );
+    }
+}
+
+pub fn save_snapshot_on_app_exit(world: &mut World) {
+    let exiting = !world.resource::<Events<AppExit>>().is_empty();
+    if let (true, Some(path)) = (exiting, world.get_resource::<SnapshotFile>()) {
+        let path = path.0.clone();
+        save(world, &path);
+    }
+}
+
+/// All snapshot files in `SNAPSHOT_DIR`, sorted by name.
+pub fn list_snapshots() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(SNAPSHOT_DIR) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .collect();
+    paths.sort();
+
+    paths
+}
@@ -0,0 +1,549 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::simulation::{species::SpeciesId, *};
+
+/// An entity stored in a `SpatialGrid`.
+#[derive(Clone, Copy)]
+pub struct SpatialEntry<T> {
+    pub entity: Entity,
+    pub position: Vec3,
+    /// Radius of a circle around `position` that covers the entity.
+    pub radius: f32,
+    pub data: T,
+}
+
+/// Entities bucketed into square cells by position, so that neighbour queries
+/// only have to look at the cells around the query point. All distances are
+/// measured in the xy-plane.
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<SpatialEntry<T>>>,
+    /// Smallest and largest occupied cell coordinates.
+    extent: Option<(IVec2, IVec2)>,
+    max_radius: f32,
+}
+
+/// What the spatial index knows about a unit.
+#[derive(Clone, Copy)]
+pub struct UnitData {
+    pub species: SpeciesId,
+    pub velocity: Vec2,
+}
+
+/// Positions of everything that units interact with. Rebuilt every tick by
+/// `index_positions`.
+#[derive(Resource)]
+pub struct SpatialIndex {
+    pub units: SpatialGrid<UnitData>,
+    pub berries: SpatialGrid<()>,
+    pub corpses: SpatialGrid<()>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        SpatialIndex {
+            units: SpatialGrid::new(SPATIAL_CELL_SIZE),
+            berries: SpatialGrid::new(SPATIAL_CELL_SIZE),
+            corpses: SpatialGrid::new(SPATIAL_CELL_SIZE),
+        }
+    }
+}
+
+impl<T: Copy> SpatialGrid<T> {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::default(),
+            extent: None,
+            max_radius: 0.0,
+        }
+    }
+
+    /// Remove all entries, keeping the cells' allocations for reuse.
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.extent = None;
+        self.max_radius = 0.0;
+    }
+
+    pub fn insert(&mut self, entry: SpatialEntry<T>) {
+        let cell = self.cell(entry.position.truncate());
+        self.extent = Some(match self.extent {
+            Some((min, max)) => (min.min(cell), max.max(cell)),
+            None => (cell, cell),
+        });
+        self.max_radius = self.max_radius.max(entry.radius);
+        self.cells.entry(cell).or_default().push(entry);
+    }
+
+    /// Radius of the largest covering circle of any entry.
+    pub fn max_radius(&self) -> f32 {
+        self.max_radius
+    }
+
+    /// Entries whose position is closer than `radius` to `center`.
+    pub fn within(&self, center: Vec2, radius: f32) -> impl Iterator<Item = &SpatialEntry<T>> {
+        let min = self.cell(center - radius);
+        let max = self.cell(center + radius);
+
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |entry| {
+                entry.position.truncate().distance_squared(center) < radius * radius
+            })
+    }
+
+    /// Entries whose covering circle overlaps the circle of `radius` around
+    /// `center`.
+    pub fn overlapping(&self, center: Vec2, radius: f32) -> impl Iterator<Item = &SpatialEntry<T>> {
+        self.within(center, radius + self.max_radius)
+            .filter(move |entry| entry.position.truncate().distance(center) < radius + entry.radius)
+    }
+
+    /// The `k` entries accepted by `filter` that are closest to `center`,
+    /// nearest first. Only entries closer than `radius` are considered.
+    pub fn k_nearest(
+        &self,
+        center: Vec2,
+        k: usize,
+        radius: f32,
+        mut filter: impl FnMut(&SpatialEntry<T>) -> bool,
+    ) -> Vec<SpatialEntry<T>> {
+        let mut found: Vec<(f32, SpatialEntry<T>)> = Vec::with_capacity(k);
+        let Some((min, max)) = self.extent.filter(|_| k > 0) else {
+            return Vec::new();
+        };
+
+        let origin = self.cell(center);
+        let last_ring = (origin - min).max(max - origin).max_element().max(0);
+
+        // Search rings of cells outwards from the one containing `center`.
+        for ring in 0..=last_ring {
+            // Every point in the ring is at least this far from `center`.
+            let ring_dist = (ring - 1).max(0) as f32 * self.cell_size;
+            if ring_dist >= radius || (found.len() == k && ring_dist * ring_dist >= found[k - 1].0)
+            {
+                break;
+            }
+
+            for cell in ring_cells(origin, ring) {
+                let Some(entries) = self.cells.get(&cell) else {
+                    continue;
+                };
+
+                for entry in entries {
+                    let dist = entry.position.truncate().distance_squared(center);
+                    if dist >= radius * radius || !filter(entry) {
+                        continue;
+                    }
+
+                    let i = found.partition_point(|(other, _)| *other <= dist);
+                    if i < k {
+                        found.insert(i, (dist, *entry));
+                        found.truncate(k);
+                    }
+                }
+            }
+        }
+
+        found.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// The entry accepted by `filter` that is closest to `center`, if any is
+    /// closer than `radius`.
+    pub fn nearest(
+        &self,
+        center: Vec2,
+        radius: f32,
+        filter: impl FnMut(&SpatialEntry<T>) -> bool,
+    ) -> Option<SpatialEntry<T>> {
+        self.k_nearest(center, 1, radius, filter).pop()
+    }
+
+    /// Like `nearest`, but also finds entries across the edges of a wrapping
+    /// `world`. Returns the entry together with the shortest offset from
+    /// `center` to it.
+    pub fn nearest_wrapped(
+        &self,
+        world: &scenario::WorldConfig,
+        center: Vec2,
+        radius: f32,
+        mut filter: impl FnMut(&SpatialEntry<T>) -> bool,
+    ) -> Option<(SpatialEntry<T>, Vec2)> {
+        world
+            .shifts(center, radius)
+            .filter_map(|shift| {
+                self.nearest(center + shift, radius, &mut filter)
+                    .map(|entry| (entry, entry.position.truncate() - shift - center))
+            })
+            .min_by(|(_, a), (_, b)| a.length_squared().total_cmp(&b.length_squared()))
+    }
+
+    /// Like `within`, but also finds entries across the edges of a wrapping
+    /// `world`. Returns every entry together with the shortest offset from
+    /// `center` to it.
+    pub fn within_wrapped(
+        &self,
+        world: &scenario::WorldConfig,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = (&SpatialEntry<T>, Vec2)> {
+        world.shifts(center, radius).flat_map(move |shift| {
+            self.within(center + shift, radius)
+                .map(move |entry| (entry, entry.position.truncate() - shift - center))
+        })
+    }
+
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+}
+
+/// Cells at Chebyshev distance `ring` from `origin`.
+fn ring_cells(origin: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+    (-ring..=ring).flat_map(move |y| {
+        // Rows between the top and bottom only have their two end cells.
+        let step = if y.abs() == ring { 1 } else { 2 * ring };
+        (-ring..=ring)
+            .step_by(step as usize)
+            .map(move |x| origin + IVec2::new(x, y))
+    })
+}
+
+pub fn index_positions(
+    mut index: ResMut<SpatialIndex>,
+    unit_query: Query<(Entity, &Transform, &unit::UnitType, &motion::MovingBody)>,
+    berry_query: Query<(Entity, &Transform), With<berry::Berry>>,
+    corpse_query: Query<(Entity, &Transform, &unit::Corpse)>,
+    registry: Res<SpeciesRegistry>,
+) {
+    index.units.clear();
+    for (entity, transform, unit, moving_body) in unit_query.iter() {
+        let body = registry.get(unit.0).body;
+        index.units.insert(SpatialEntry {
+            entity,
+            position: transform.translation,
+            radius: (transform.scale.truncate() * Vec2::new(body.width, body.height)).length(),
+            data: UnitData {
+                species: unit.0,
+                velocity: moving_body.curr_velocity.truncate(),
+            },
+        });
+    }
+
+    index.berries.clear();
+    for (entity, transform) in berry_query.iter() {
+        index.berries.insert(SpatialEntry {
+            entity,
+            position: transform.translation,
+            radius: (transform.scale.truncate()
+                * Vec2::new(BERRY_RENDER_WIDTH, BERRY_RENDER_HEIGHT))
+            .length(),
+            data: (),
+        });
+    }
+
+    index.corpses.clear();
+    for (entity, transform, corpse) in corpse_query.iter() {
+        let body = registry.get(corpse.unit.0).body;
+        index.corpses.insert(SpatialEntry {
+            entity,
+            position: transform.translation,
+            radius: (transform.scale.truncate() * Vec2::new(body.width, body.height)).length(),
+            data: (),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const CELL_SIZE: f32 = 100.0;
+
+    /// Random points in a square of half side `half_side`, a quarter of them
+    /// snapped onto cell edges and corners.
+    fn points(rng: &mut ChaCha8Rng, n: usize, half_side: f32) -> Vec<Vec2> {
+        (0..n)
+            .map(|i| {
+                let point = Vec2::new(
+                    rng.gen_range(-half_side..half_side),
+                    rng.gen_range(-half_side..half_side),
+                );
+                if i % 4 == 0 {
+                    (point / CELL_SIZE).round() * CELL_SIZE
+                } else {
+                    point
+                }
+            })
+            .collect()
+    }
+
+    fn grid(points: &[Vec2]) -> SpatialGrid<()> {
+        let mut grid = SpatialGrid::new(CELL_SIZE);
+        for (i, point) in points.iter().enumerate() {
+            grid.insert(SpatialEntry {
+                entity: Entity::from_raw(i as u32),
+                position: point.extend(0.0),
+                radius: 0.0,
+                data: (),
+            });
+        }
+        grid
+    }
+
+    /// Indices of the entries of `found`, sorted.
+    fn indices<'a>(found: impl IntoIterator<Item = &'a SpatialEntry<()>>) -> Vec<u32> {
+        let mut indices: Vec<u32> = found
+            .into_iter()
+            .map(|entry| entry.entity.index())
+            .collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    /// Indices of `points` whose `offset` is shorter than `radius`, together
+    /// with its length, nearest first.
+    fn scan(points: &[Vec2], radius: f32, offset: impl Fn(Vec2) -> Vec2) -> Vec<(u32, f32)> {
+        let mut found: Vec<(u32, f32)> = points
+            .iter()
+            .enumerate()
+            .map(|(i, point)| (i as u32, offset(*point).length()))
+            .filter(|(_, dist)| *dist < radius)
+            .collect();
+        found.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        found
+    }
+
+    fn wrapping_world(half_side: f32) -> scenario::WorldConfig {
+        scenario::WorldConfig {
+            bounds: scenario::Bounds {
+                x0: -half_side,
+                x1: half_side,
+                y0: -half_side,
+                y1: half_side,
+            },
+            boundary: scenario::Boundary::Wrap,
+            ..Scenario::default().world
+        }
+    }
+
+    #[test]
+    fn within_matches_linear_scan() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let points = points(&mut rng, 500, 600.0);
+        let grid = grid(&points);
+
+        for center in self::points(&mut rng, 100, 700.0) {
+            for radius in [1.0, 50.0, CELL_SIZE, 250.0] {
+                let mut expected: Vec<u32> = scan(&points, radius, |p| p - center)
+                    .into_iter()
+                    .map(|(i, _)| i)
+                    .collect();
+                expected.sort_unstable();
+                assert_eq!(indices(grid.within(center, radius)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn k_nearest_matches_linear_scan() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let points = points(&mut rng, 300, 800.0);
+        let grid = grid(&points);
+
+        // Centers outside the occupied cells too, where the search starts
+        // from an empty ring.
+        for center in self::points(&mut rng, 100, 1200.0) {
+            for radius in [30.0, CELL_SIZE, 350.0, 5000.0] {
+                for k in [1, 3, 10] {
+                    // Only every other entry is accepted.
+                    let expected: Vec<f32> = scan(&points, radius, |p| p - center)
+                        .into_iter()
+                        .filter(|(i, _)| i % 2 == 0)
+                        .take(k)
+                        .map(|(_, dist)| dist)
+                        .collect();
+                    let found: Vec<f32> = grid
+                        .k_nearest(center, k, radius, |entry| entry.entity.index() % 2 == 0)
+                        .iter()
+                        .map(|entry| entry.position.truncate().distance(center))
+                        .collect();
+                    // Ties may be broken either way, so compare distances.
+                    assert_eq!(found, expected, "center {center}, radius {radius}, k {k}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_wrapped_matches_linear_scan() {
+        let half_side = 500.0;
+        let world = wrapping_world(half_side);
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let points = points(&mut rng, 60, half_side);
+        let grid = grid(&points);
+
+        // Centers near the seam find entries on the other side.
+        let centers = self::points(&mut rng, 100, half_side)
+            .into_iter()
+            .map(|center| center.signum() * (half_side - center.abs() / 10.0))
+            .chain(self::points(&mut rng, 50, half_side));
+        for center in centers {
+            for radius in [CELL_SIZE, 300.0] {
+                let expected = scan(&points, radius, |p| world.offset(center, p))
+                    .first()
+                    .map(|(_, dist)| *dist);
+                let found = grid
+                    .nearest_wrapped(&world, center, radius, |_| true)
+                    .map(|(_, offset)| offset.length());
+
+                assert_eq!(found.is_some(), expected.is_some(), "center {center}");
+                if let (Some(found), Some(expected)) = (found, expected) {
+                    assert!((found - expected).abs() < 1e-3, "center {center}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn within_wrapped_matches_linear_scan() {
+        let half_side = 500.0;
+        let world = wrapping_world(half_side);
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let points = points(&mut rng, 200, half_side);
+        let grid = grid(&points);
+
+        let centers = self::points(&mut rng, 100, half_side)
+            .into_iter()
+            .map(|center| center.signum() * (half_side - center.abs() / 10.0))
+            .chain(self::points(&mut rng, 50, half_side));
+        for center in centers {
+            let radius = 150.0;
+            let mut expected: Vec<(u32, Vec2)> = scan(&points, radius, |p| world.offset(center, p))
+                .into_iter()
+                .map(|(i, _)| (i, world.offset(center, points[i as usize])))
+                .collect();
+            expected.sort_by_key(|(i, _)| *i);
+            let mut found: Vec<(u32, Vec2)> = grid
+                .within_wrapped(&world, center, radius)
+                .map(|(entry, offset)| (entry.entity.index(), offset))
+                .collect();
+            found.sort_by_key(|(i, _)| *i);
+
+            assert_eq!(found.len(), expected.len(), "center {center}");
+            for ((i, found), (j, expected)) in found.iter().zip(&expected) {
+                assert_eq!(i, j, "center {center}");
+                assert!(found.abs_diff_eq(*expected, 1e-3), "center {center}");
+            }
+        }
+    }
+
+    /// Time the queries the simulation makes against the spatial index and
+    /// against a linear scan over all entities, for growing numbers of entities
+    /// at the density of the default scenario, and print the results.
+    #[test]
+    #[ignore = "prints timings, run with --ignored --nocapture"]
+    fn benchmark() {
+        const SENSOR_RANGE: f32 = 500.0;
+        /// About the distance at which two of the largest bodies collide.
+        const COLLISION_RANGE: f32 = 50.0;
+
+        let density = NUM_FERNWORMS as f32
+            / ((PLAYABLE_AREA_X1 - PLAYABLE_AREA_X0) * (PLAYABLE_AREA_Y1 - PLAYABLE_AREA_Y0));
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        println!(
+            "{:>8} {:>12} {:>14} {:>14} {:>14} {:>14}",
+            "entities", "build", "grid radius", "scan radius", "grid nearest", "scan nearest"
+        );
+
+        for n in [100, 1_000, 10_000, 100_000] {
+            let half_side = (n as f32 / density).sqrt() / 2.0;
+            let positions: Vec<Vec3> = (0..n)
+                .map(|_| {
+                    Vec3::new(
+                        rng.gen_range(-half_side..half_side),
+                        rng.gen_range(-half_side..half_side),
+                        0.0,
+                    )
+                })
+                .collect();
+
+            let start = Instant::now();
+            let mut grid = SpatialGrid::new(SPATIAL_CELL_SIZE);
+            for (i, position) in positions.iter().enumerate() {
+                grid.insert(SpatialEntry {
+                    entity: Entity::from_raw(i as u32),
+                    position: *position,
+                    radius: 0.0,
+                    data: (),
+                });
+            }
+            let build = start.elapsed();
+
+            let grid_radius = time_queries(&positions, |center| {
+                grid.within(center, COLLISION_RANGE).count()
+            });
+            let grid_nearest = time_queries(&positions, |center| {
+                grid.nearest(center, SENSOR_RANGE, |entry| {
+                    entry.position.truncate() != center
+                })
+                .map_or(0, |entry| entry.entity.index() as usize)
+            });
+
+            // A linear scan does n queries over n entities, so skip it once it
+            // would take minutes.
+            let (scan_radius, scan_nearest) = if n <= 10_000 {
+                let scan_radius = time_queries(&positions, |center| {
+                    positions
+                        .iter()
+                        .filter(|p| p.truncate().distance_squared(center) < COLLISION_RANGE.powi(2))
+                        .count()
+                });
+                let scan_nearest = time_queries(&positions, |center| {
+                    positions
+                        .iter()
+                        .enumerate()
+                        .map(|(i, p)| (i, p.truncate().distance_squared(center)))
+                        .filter(|(_, dist)| *dist > 0.0 && *dist < SENSOR_RANGE.powi(2))
+                        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                        .map_or(0, |(i, _)| i)
+                });
+                (format!("{scan_radius:?}"), format!("{scan_nearest:?}"))
+            } else {
+                ("-".to_string(), "-".to_string())
+            };
+
+            println!(
+                "{n:>8} {:>12} {:>14} {:>14} {:>14} {:>14}",
+                format!("{build:?}"),
+                format!("{grid_radius:?}"),
+                scan_radius,
+                format!("{grid_nearest:?}"),
+                scan_nearest
+            );
+        }
+    }
+
+    /// Total time to run `query` once around every position.
+    fn time_queries(positions: &[Vec3], mut query: impl FnMut(Vec2) -> usize) -> Duration {
+        let start = Instant::now();
+        let mut total: usize = 0;
+        for position in positions {
+            total = total.wrapping_add(query(position.truncate()));
+        }
+        std::hint::black_box(total);
+
+        start.elapsed()
+    }
+}
@@ -0,0 +1,445 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{asset::io::file::FileAssetReader, prelude::*};
+use serde::Deserialize;
+
+use crate::simulation::{
+    brain::BrainConfig,
+    genome::{Genome, Mutation},
+    senses::Senses,
+};
+
+/// Name used in a diet to refer to berries rather than to another species.
+const BERRY_FOOD: &str = "berry";
+/// Name used in a diet to refer to corpses of any species.
+const CARRION_FOOD: &str = "carrion";
+
+/// Index of a species in the `SpeciesRegistry`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SpeciesId(pub usize);
+
+/// A species definition as written in an asset file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpeciesFile {
+    name: String,
+    sprite: String,
+    corpse_sprite: String,
+    sprite_size: Size,
+    scale: f32,
+    depth: f32,
+    mouth: Mouth,
+    body: Size,
+    max_speed: f32,
+    max_acceleration: f32,
+    max_fullness: f32,
+    drain_per_unit_traveled: f32,
+    sensor_range: f32,
+    /// Full angle of the view cone in degrees. Defaults to all around.
+    #[serde(default)]
+    field_of_view: Option<f32>,
+    #[serde(default)]
+    line_of_sight: bool,
+    nutrition: f32,
+    diet: Vec<String>,
+    #[serde(default)]
+    predators: Vec<String>,
+    #[serde(default)]
+    reproduction: Option<Reproduction>,
+    #[serde(default)]
+    mutation: Option<Mutation>,
+    #[serde(default)]
+    brain: BrainConfig,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Size {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Area a unit eats with. Given in world units for a unit with scale 1.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Mouth {
+    /// Distance from the center of the unit along its heading.
+    pub offset: f32,
+    /// Half extents of the mouth's bounding box.
+    pub size: Size,
+}
+
+/// How units of a species reproduce. Species without it never reproduce.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Reproduction {
+    /// Fraction of the maximum fullness a unit must stay above to reproduce.
+    pub fullness_threshold: f32,
+    /// Seconds a unit must stay above the threshold before reproducing.
+    pub time_above_threshold: f32,
+    /// Fullness spent by the parent. It is shared equally by the offspring.
+    pub cost: f32,
+    /// Seconds after reproducing before a unit can reproduce again.
+    pub cooldown: f32,
+    pub litter_size: u32,
+    /// Maximum distance from the parent that offspring are spawned at.
+    pub spawn_radius: f32,
+}
+
+/// What a species can eat.
+#[derive(Clone, Default)]
+pub struct Diet {
+    pub berries: bool,
+    pub carrion: bool,
+    pub prey: Vec<SpeciesId>,
+}
+
+/// A species definition with all references to other species resolved.
+pub struct Species {
+    pub name: String,
+    pub sprite: String,
+    pub corpse_sprite: String,
+    /// Size of the sprite in pixels.
+    pub sprite_size: Size,
+    /// Factor from sprite pixels to world units.
+    pub scale: f32,
+    /// Z coordinate of units of this species.
+    pub depth: f32,
+    pub mouth: Mouth,
+    /// Half extents of the bounding box that predators must reach to eat
+    /// units of this species. Given in world units for a unit with scale 1.
+    pub body: Size,
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    pub max_fullness: f32,
+    pub drain_per_unit_traveled: f32,
+    pub sensor_range: f32,
+    /// Full angle of the view cone around the heading, in radians.
+    pub field_of_view: f32,
+    /// Whether obstacles block the view.
+    pub line_of_sight: bool,
+    /// Fullness gained by a predator eating a unit of this species, and the
+    /// biomass of its corpse.
+    pub nutrition: f32,
+    pub diet: Diet,
+    pub predators: Vec<SpeciesId>,
+    pub reproduction: Option<Reproduction>,
+    /// How offspring genomes differ from their parent's. Offspring are exact
+    /// copies if there is none.
+    pub mutation: Option<Mutation>,
+    /// Brain of units of this species that have not inherited one.
+    pub brain: BrainConfig,
+}
+
+impl Species {
+    /// Genome of units of this species that have not inherited one.
+    pub fn default_genome(&self) -> Genome {
+        Genome {
+            max_speed: self.max_speed,
+            max_acceleration: self.max_acceleration,
+            max_fullness: self.max_fullness,
+            drain_per_unit_traveled: self.drain_per_unit_traveled,
+            body_scale: 1.0,
+            sensor_range: self.sensor_range,
+            brain_weights: Vec::new(),
+        }
+    }
+
+    /// Senses of units of this species with `genome`.
+    pub fn senses(&self, genome: &Genome) -> Senses {
+        Senses {
+            vision_radius: genome.sensor_range,
+            field_of_view: self.field_of_view,
+            line_of_sight: self.line_of_sight,
+        }
+    }
+
+    /// Size of the unit's sprite in world units.
+    pub fn render_size(&self) -> Vec2 {
+        Vec2::new(self.sprite_size.width, self.sprite_size.height) * self.scale
+    }
+}
+
+/// All species known to the simulation, loaded from `species_dir` at startup.
+#[derive(Resource)]
+pub struct SpeciesRegistry {
+    species: Vec<Species>,
+}
+
+#[derive(Debug)]
+pub enum SpeciesError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Invalid { field: String, reason: String },
+}
+
+impl fmt::Display for SpeciesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpeciesError::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            SpeciesError::Parse(path, err) => write!(f, "{}:{err}", path.display()),
+            SpeciesError::Invalid { field, reason } => write!(f, "invalid '{field}': {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SpeciesError {}
+
+impl SpeciesRegistry {
+    /// Load every species file in `dir`. Species are ordered by file name, so
+    /// that their ids are the same between runs.
+    pub fn load(dir: &Path) -> Result<Self, SpeciesError> {
+        let entries = fs::read_dir(dir).map_err(|err| SpeciesError::Io(dir.to_path_buf(), err))?;
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        paths.sort();
+
+        let mut files = Vec::new();
+        for path in paths {
+            let text =
+                fs::read_to_string(&path).map_err(|err| SpeciesError::Io(path.clone(), err))?;
+            let file: SpeciesFile =
+                ron::from_str(&text).map_err(|err| SpeciesError::Parse(path.clone(), err))?;
+            files.push(file);
+        }
+
+        Self::from_files(files)
+    }
+
+    fn from_files(files: Vec<SpeciesFile>) -> Result<Self, SpeciesError> {
+        if files.is_empty() {
+            return Err(invalid("species", "no species defined"));
+        }
+
+        for (i, file) in files.iter().enumerate() {
+            file.validate()?;
+            if [BERRY_FOOD, CARRION_FOOD].contains(&file.name.as_str())
+                || files[..i].iter().any(|other| other.name == file.name)
+            {
+                return Err(invalid(
+                    &format!("{}.name", file.name),
+                    "name is already taken",
+                ));
+            }
+        }
+
+        let id_of = |name: &str| {
+            files
+                .iter()
+                .position(|file| file.name == name)
+                .map(SpeciesId)
+        };
+
+        let mut species = Vec::new();
+        for file in &files {
+            let mut diet = Diet::default();
+            for (i, food) in file.diet.iter().enumerate() {
+                if food == BERRY_FOOD {
+                    diet.berries = true;
+                } else if food == CARRION_FOOD {
+                    diet.carrion = true;
+                } else {
+                    let id = id_of(food).ok_or_else(|| {
+                        invalid(&format!("{}.diet[{i}]", file.name), "unknown species")
+                    })?;
+                    diet.prey.push(id);
+                }
+            }
+
+            let mut predators = Vec::new();
+            for (i, predator) in file.predators.iter().enumerate() {
+                let id = id_of(predator).ok_or_else(|| {
+                    invalid(&format!("{}.predators[{i}]", file.name), "unknown species")
+                })?;
+                predators.push(id);
+            }
+
+            species.push(Species {
+                name: file.name.clone(),
+                sprite: file.sprite.clone(),
+                corpse_sprite: file.corpse_sprite.clone(),
+                sprite_size: file.sprite_size,
+                scale: file.scale,
+                depth: file.depth,
+                mouth: file.mouth,
+                body: file.body,
+                max_speed: file.max_speed,
+                max_acceleration: file.max_acceleration,
+                max_fullness: file.max_fullness,
+                drain_per_unit_traveled: file.drain_per_unit_traveled,
+                sensor_range: file.sensor_range,
+                field_of_view: file.field_of_view.unwrap_or(360.0).to_radians(),
+                line_of_sight: file.line_of_sight,
+                nutrition: file.nutrition,
+                diet,
+                predators,
+                reproduction: file.reproduction,
+                mutation: file.mutation,
+                brain: file.brain.clone(),
+            });
+        }
+
+        let registry = SpeciesRegistry { species };
+        registry.warn_inconsistent_predators();
+
+        Ok(registry)
+    }
+
+    /// All species ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = (SpeciesId, &Species)> {
+        self.species
+            .iter()
+            .enumerate()
+            .map(|(id, species)| (SpeciesId(id), species))
+    }
+
+    pub fn get(&self, id: SpeciesId) -> &Species {
+        &self.species[id.0]
+    }
+
+    pub fn id(&self, name: &str) -> Option<SpeciesId> {
+        self.species
+            .iter()
+            .position(|species| species.name == name)
+            .map(SpeciesId)
+    }
+
+    /// Predators are only informational, eating is decided by diets. Point out
+    /// where the two disagree since it is most likely a mistake.
+    fn warn_inconsistent_predators(&self) {
+        for (id, species) in self.species.iter().enumerate() {
+            for predator in &species.predators {
+                if !self.get(*predator).diet.prey.contains(&SpeciesId(id)) {
+                    warn!(
+                        "{} lists {} as a predator, but it is not in the diet of {}",
+                        species.name,
+                        self.get(*predator).name,
+                        self.get(*predator).name
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl SpeciesFile {
+    fn validate(&self) -> Result<(), SpeciesError> {
+        for (field, value) in [
+            ("sprite_size.width", self.sprite_size.width),
+            ("sprite_size.height", self.sprite_size.height),
+            ("scale", self.scale),
+            ("max_speed", self.max_speed),
+            ("max_acceleration", self.max_acceleration),
+            ("max_fullness", self.max_fullness),
+            ("sensor_range", self.sensor_range),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(invalid(
+                    &format!("{}.{field}", self.name),
+                    "must be a positive number",
+                ));
+            }
+        }
+
+        for (field, value) in [("depth", self.depth), ("mouth.offset", self.mouth.offset)] {
+            if !value.is_finite() {
+                return Err(invalid(
+                    &format!("{}.{field}", self.name),
+                    "must be a finite number",
+                ));
+            }
+        }
+
+        for (field, value) in [
+            ("mouth.size.width", self.mouth.size.width),
+            ("mouth.size.height", self.mouth.size.height),
+            ("body.width", self.body.width),
+            ("body.height", self.body.height),
+            ("drain_per_unit_traveled", self.drain_per_unit_traveled),
+            ("nutrition", self.nutrition),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(invalid(
+                    &format!("{}.{field}", self.name),
+                    "must be a non-negative number",
+                ));
+            }
+        }
+
+        if let Some(field_of_view) = self.field_of_view {
+            if !(field_of_view > 0.0 && field_of_view <= 360.0) {
+                return Err(invalid(
+                    &format!("{}.field_of_view", self.name),
+                    "must be greater than 0 and at most 360",
+                ));
+            }
+        }
+
+        if let Some(reproduction) = &self.reproduction {
+            reproduction.validate(&self.name, self.max_fullness)?;
+        }
+
+        if let Some(field) = self.mutation.as_ref().and_then(Mutation::invalid_field) {
+            return Err(invalid(
+                &format!("{}.mutation.{field}", self.name),
+                "out of range",
+            ));
+        }
+
+        if let Some((field, reason)) = self.brain.invalid_field() {
+            return Err(invalid(&format!("{}.brain.{field}", self.name), reason));
+        }
+
+        Ok(())
+    }
+}
+
+impl Reproduction {
+    fn validate(&self, species: &str, max_fullness: f32) -> Result<(), SpeciesError> {
+        let field = |name: &str| format!("{species}.reproduction.{name}");
+
+        if !(self.fullness_threshold > 0.0 && self.fullness_threshold <= 1.0) {
+            return Err(invalid(
+                &field("fullness_threshold"),
+                "must be greater than 0 and at most 1",
+            ));
+        }
+        if !(self.cost > 0.0 && self.cost <= max_fullness) {
+            return Err(invalid(
+                &field("cost"),
+                "must be positive and at most max_fullness",
+            ));
+        }
+        if self.litter_size == 0 {
+            return Err(invalid(&field("litter_size"), "must be at least 1"));
+        }
+        for (name, value) in [
+            ("time_above_threshold", self.time_above_threshold),
+            ("cooldown", self.cooldown),
+            ("spawn_radius", self.spawn_radius),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(invalid(&field(name), "must be a non-negative number"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Directory holding the species definitions.
+pub fn species_dir() -> PathBuf {
+    FileAssetReader::get_base_path().join("assets/species")
+}
+
+fn invalid(field: &str, reason: &str) -> SpeciesError {
+    SpeciesError::Invalid {
+        field: field.to_string(),
+        reason: reason.to_string(),
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+
+use crate::simulation::*;
+
+/// How fast the simulation runs relative to real time.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpeedSetting {
+    Scaled(f32),
+    /// As many ticks per frame as fit in `MAX_SPEED_FRAME_SECS`.
+    Max,
+}
+
+/// Speed chosen with the keyboard. Faster speeds run more fixed ticks per
+/// frame, so every tick still advances the simulation by the same timestep.
+#[derive(Resource)]
+pub struct SimSpeed {
+    pub setting: SpeedSetting,
+    pub paused: bool,
+    /// Single ticks still to run while paused, one per frame.
+    pub steps: u32,
+    /// Ticks per frame at `SpeedSetting::Max`, adapted to the frame time.
+    max_ticks: u32,
+    /// Simulated seconds per real second, smoothed over recent frames.
+    actual: f32,
+    last_tick: u64,
+}
+
+/// Text showing the current speed.
+#[derive(Component)]
+pub struct SpeedText;
+
+impl Default for SimSpeed {
+    fn default() -> Self {
+        SimSpeed {
+            setting: SpeedSetting::Scaled(1.0),
+            paused: false,
+            steps: 0,
+            max_ticks: 1,
+            actual: 1.0,
+            last_tick: 0,
+        }
+    }
+}
+
+pub fn setup(mut commands: Commands, game_data: Res<SimData>) {
+    commands.insert_resource(SimSpeed {
+        last_tick: game_data.tick,
+        ..default()
+    });
+
+    commands.spawn((
+        SimulationComponent,
+        SpeedText,
+        Text::new(""),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.),
+            top: Val::Px(10.),
+            ..default()
+        },
+    ));
+}
+
+/// Leave virtual time running normally for the other states.
+pub fn exit(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+    time.set_relative_speed(1.0);
+}
+
+/// Decide how much virtual time the fixed loop gets to consume this frame.
+/// Runs right after the clocks are updated, so changes to the relative speed
+/// and pausing take effect from the next frame on.
+pub fn apply_speed(
+    mut speed: ResMut<SimSpeed>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    fixed_time: Res<Time<Fixed>>,
+    real_time: Res<Time<Real>>,
+) {
+    let timestep = fixed_time.timestep();
+
+    if speed.paused {
+        virtual_time.pause();
+        if speed.steps > 0 {
+            speed.steps -= 1;
+            virtual_time.advance_by(timestep);
+        }
+        return;
+    }
+
+    match speed.setting {
+        SpeedSetting::Scaled(scale) => {
+            virtual_time.unpause();
+            virtual_time.set_relative_speed(scale);
+        }
+        SpeedSetting::Max => {
+            // Virtual time is advanced by hand instead of through its speed,
+            // so that slow frames do not snowball into even more ticks.
+            virtual_time.pause();
+            speed.max_ticks = if real_time.delta_secs() < MAX_SPEED_FRAME_SECS {
+                speed.max_ticks + speed.max_ticks.div_ceil(8)
+            } else {
+                (speed.max_ticks - speed.max_ticks / 8).max(1)
+            };
+            virtual_time.advance_by(timestep * speed.max_ticks);
+        }
+    }
+}
+
+pub fn update_speed_text(
+    mut speed: ResMut<SimSpeed>,
+    mut text: Single<&mut Text, With<SpeedText>>,
+    game_data: Res<SimData>,
+    fixed_time: Res<Time<Fixed>>,
+    real_time: Res<Time<Real>>,
+) {
+    let ticks = game_data.tick - speed.last_tick;
+    speed.last_tick = game_data.tick;
+    if real_time.delta_secs() > 0.0 {
+        let actual = ticks as f32 * fixed_time.timestep().as_secs_f32() / real_time.delta_secs();
+        speed.actual += (actual - speed.actual) * 0.05;
+    }
+
+    text.0 = match (speed.paused, speed.setting) {
+        (true, _) => "Paused".to_string(),
+        (false, SpeedSetting::Scaled(scale)) => format!("{scale}x ({:.1}x)", speed.actual),
+        (false, SpeedSetting::Max) => format!("Max ({:.1}x)", speed.actual),
+    };
+}
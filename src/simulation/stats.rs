@@ -0,0 +1,226 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use crate::simulation::*;
+
+/// File that the statistics are written to when the simulation ends.
+#[derive(Resource)]
+pub struct StatsFile(pub PathBuf);
+
+/// Request to write the statistics recorded so far to the `StatsFile`, or to
+/// `DEFAULT_STATS_FILE` if there is none.
+#[derive(Event)]
+pub struct WriteStats;
+
+/// Population statistics sampled every `interval` ticks.
+#[derive(Resource)]
+pub struct StatsRecorder {
+    interval: u64,
+    /// Births and deaths per species since the last sample.
+    events: Vec<EventCounts>,
+    samples: Vec<Sample>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct EventCounts {
+    births: u64,
+    starved: u64,
+    eaten: u64,
+}
+
+struct Sample {
+    tick: u64,
+    species: Vec<SpeciesSample>,
+    berries: usize,
+    corpses: usize,
+}
+
+struct SpeciesSample {
+    count: usize,
+    fullness_mean: Option<f32>,
+    fullness_variance: Option<f32>,
+    events: EventCounts,
+}
+
+impl Default for StatsRecorder {
+    fn default() -> Self {
+        StatsRecorder::new(STATS_INTERVAL)
+    }
+}
+
+impl StatsRecorder {
+    pub fn new(interval: u64) -> Self {
+        StatsRecorder {
+            interval,
+            events: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self, species_count: usize) {
+        self.events = vec![EventCounts::default(); species_count];
+        self.samples.clear();
+    }
+
+    /// Write all samples as CSV with one row per sample. Births and deaths
+    /// are counted since the previous row.
+    fn write_csv(&self, path: &Path, registry: &SpeciesRegistry, tick_secs: f32) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        write!(out, "tick,time")?;
+        for (_, species) in registry.iter() {
+            let name = &species.name;
+            write!(
+                out,
+                ",{name}_count,{name}_fullness_mean,{name}_fullness_variance,\
+                 {name}_births,{name}_starved,{name}_eaten"
+            )?;
+        }
+        writeln!(out, ",berries,corpses")?;
+
+        for sample in &self.samples {
+            write!(out, "{},{}", sample.tick, sample.tick as f32 * tick_secs)?;
+            for species in &sample.species {
+                write!(
+                    out,
+                    ",{},{},{},{},{},{}",
+                    species.count,
+                    optional(species.fullness_mean),
+                    optional(species.fullness_variance),
+                    species.events.births,
+                    species.events.starved,
+                    species.events.eaten
+                )?;
+            }
+            writeln!(out, ",{},{}", sample.berries, sample.corpses)?;
+        }
+
+        out.flush()
+    }
+}
+
+/// Empty for missing values, so that plotting tools leave a gap.
+fn optional(value: Option<f32>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+pub fn reset_stats(mut recorder: ResMut<StatsRecorder>, registry: Res<SpeciesRegistry>) {
+    recorder.reset(registry.iter().count());
+}
+
+/// Count this tick's births and deaths and take a sample if it is due.
+pub fn record_stats(
+    mut recorder: ResMut<StatsRecorder>,
+    game_data: Res<SimData>,
+    mut births: EventReader<unit::BirthEvent>,
+    mut deaths: EventReader<unit::DeathEvent>,
+    unit_query: Query<(&unit::UnitType, &hunger::Hunger)>,
+    berry_query: Query<(), With<berry::Berry>>,
+    corpse_query: Query<(), With<unit::Corpse>>,
+) {
+    for birth in births.read() {
+        recorder.events[birth.unit.0 .0].births += 1;
+    }
+    for death in deaths.read() {
+        let counts = &mut recorder.events[death.unit.0 .0];
+        match death.cause {
+            unit::DeathCause::Starvation => counts.starved += 1,
+            unit::DeathCause::Predation => counts.eaten += 1,
+        }
+    }
+
+    let tick = game_data.tick;
+    if !tick.is_multiple_of(recorder.interval) {
+        return;
+    }
+
+    let mut fullness: Vec<Vec<f32>> = vec![Vec::new(); recorder.events.len()];
+    for (unit, hunger) in unit_query.iter() {
+        fullness[unit.0 .0].push(hunger.curr_fullness);
+    }
+
+    let species = fullness
+        .iter()
+        .zip(&recorder.events)
+        .map(|(fullness, events)| {
+            let count = fullness.len();
+            let mean = (count > 0).then(|| fullness.iter().sum::<f32>() / count as f32);
+            let variance = mean.map(|mean| {
+                fullness.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / count as f32
+            });
+
+            SpeciesSample {
+                count,
+                fullness_mean: mean,
+                fullness_variance: variance,
+                events: *events,
+            }
+        })
+        .collect();
+
+    recorder.samples.push(Sample {
+        tick,
+        species,
+        berries: berry_query.iter().count(),
+        corpses: corpse_query.iter().count(),
+    });
+    recorder.events.fill(EventCounts::default());
+}
+
+/// Write the statistics to the `StatsFile` when going back to the menu.
+pub fn write_stats_on_exit(
+    recorder: Res<StatsRecorder>,
+    file: Option<Res<StatsFile>>,
+    registry: Res<SpeciesRegistry>,
+    time: Res<Time<Fixed>>,
+) {
+    if let Some(file) = file {
+        write_stats(&recorder, &file.0, &registry, &time);
+    }
+}
+
+/// Closing the app does not exit the simulation state, so the statistics have
+/// to be written here as well.
+pub fn write_stats_on_app_exit(
+    mut app_exit: EventReader<AppExit>,
+    recorder: Res<StatsRecorder>,
+    file: Option<Res<StatsFile>>,
+    registry: Res<SpeciesRegistry>,
+    time: Res<Time<Fixed>>,
+) {
+    if let (Some(_), Some(file)) = (app_exit.read().next(), file) {
+        write_stats(&recorder, &file.0, &registry, &time);
+    }
+}
+
+pub fn write_stats_on_request(
+    mut requests: EventReader<WriteStats>,
+    recorder: Res<StatsRecorder>,
+    file: Option<Res<StatsFile>>,
+    registry: Res<SpeciesRegistry>,
+    time: Res<Time<Fixed>>,
+) {
+    if requests.read().count() == 0 {
+        return;
+    }
+
+    let path = file.map_or_else(|| PathBuf::from(DEFAULT_STATS_FILE), |file| file.0.clone());
+    write_stats(&recorder, &path, &registry, &time);
+}
+
+fn write_stats(
+    recorder: &StatsRecorder,
+    path: &Path,
+    registry: &SpeciesRegistry,
+    time: &Time<Fixed>,
+) {
+    match recorder.write_csv(path, registry, time.timestep().as_secs_f32()) {
+        Ok(()) => info!("Wrote statistics to {}", path.display()),
+        Err(err) => error!("Failed to write statistics to {}: {err}", path.display()),
+    }
+}
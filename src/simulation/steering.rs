@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Seconds in which steering tries to reach the velocity it wants, as far as
+/// the maximum acceleration allows.
+const RESPONSE_TIME: f32 = 0.1;
+/// Longest time ahead that `pursue` and `evade` predict another unit's
+/// position for.
+const MAX_PREDICTION_TIME: f32 = 2.0;
+
+/// The unit that is being steered.
+#[derive(Clone, Copy)]
+pub struct Agent {
+    pub velocity: Vec2,
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    pub radius: f32,
+}
+
+/// Something the agent reacts to, relative to the agent's position.
+#[derive(Clone, Copy)]
+pub struct Neighbour {
+    pub offset: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+}
+
+/// A steering behaviour as combined by a `brain::Brain::Steering`.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub enum Behaviour {
+    /// Head straight for the nearest food.
+    SeekFood,
+    /// Head for where the nearest food is going to be.
+    PursueFood,
+    /// Head for the nearest food, slowing down within `slowing_radius` of it.
+    ArriveFood { slowing_radius: f32 },
+    /// Run straight away from the nearest predator.
+    FleePredators,
+    /// Run away from where the nearest predator is going to be.
+    EvadePredators,
+    /// Roam with a randomly drifting heading. `turn_rate` is the standard
+    /// deviation of the drift, in radians per square root second.
+    Wander { turn_rate: f32 },
+    /// Steer around bodies in the way.
+    AvoidObstacles,
+    /// Keep some distance from nearby units of the same species.
+    Separation,
+    /// Move in the same direction as nearby units of the same species.
+    Alignment,
+    /// Stay close to nearby units of the same species.
+    Cohesion,
+}
+
+impl Behaviour {
+    /// Whether the behaviour goes for food.
+    pub fn is_food(&self) -> bool {
+        matches!(
+            self,
+            Behaviour::SeekFood | Behaviour::PursueFood | Behaviour::ArriveFood { .. }
+        )
+    }
+
+    /// Returns the name of the first invalid field and why, if any.
+    pub fn invalid_field(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Behaviour::ArriveFood { slowing_radius } => (!(slowing_radius.is_finite()
+                && *slowing_radius > 0.0))
+                .then_some(("slowing_radius", "must be a positive number")),
+            Behaviour::Wander { turn_rate } => (!(turn_rate.is_finite() && *turn_rate >= 0.0))
+                .then_some(("turn_rate", "must be a non-negative number")),
+            _ => None,
+        }
+    }
+}
+
+/// Acceleration that changes the agent's velocity to `desired`.
+fn steer_towards(agent: &Agent, desired: Vec2) -> Vec2 {
+    ((desired - agent.velocity) / RESPONSE_TIME).clamp_length_max(agent.max_acceleration)
+}
+
+/// Go at full speed towards `offset`.
+pub fn seek(agent: &Agent, offset: Vec2) -> Vec2 {
+    steer_towards(agent, offset.normalize_or_zero() * agent.max_speed)
+}
+
+/// Go at full speed away from `offset`.
+pub fn flee(agent: &Agent, offset: Vec2) -> Vec2 {
+    seek(agent, -offset)
+}
+
+/// Go towards `offset`, slowing down to stop there once within
+/// `slowing_radius` of it.
+pub fn arrive(agent: &Agent, offset: Vec2, slowing_radius: f32) -> Vec2 {
+    let speed = agent.max_speed * (offset.length() / slowing_radius).min(1.0);
+    steer_towards(agent, offset.normalize_or_zero() * speed)
+}
+
+/// Where `other` will be by the time the agent could get there.
+fn predict(agent: &Agent, other: &Neighbour) -> Vec2 {
+    let time = other.offset.length() / (agent.max_speed + other.velocity.length());
+    other.offset + other.velocity * time.min(MAX_PREDICTION_TIME)
+}
+
+/// Go towards where `target` is heading.
+pub fn pursue(agent: &Agent, target: &Neighbour) -> Vec2 {
+    seek(agent, predict(agent, target))
+}
+
+/// Go away from where `threat` is heading.
+pub fn evade(agent: &Agent, threat: &Neighbour) -> Vec2 {
+    flee(agent, predict(agent, threat))
+}
+
+/// Go in the direction of `heading`.
+pub fn wander(agent: &Agent, heading: f32) -> Vec2 {
+    seek(agent, Vec2::from_angle(heading))
+}
+
+/// Steer sideways around the closest of `obstacles` that the agent would hit
+/// within `look_ahead` of its current position, harder the closer it is.
+pub fn avoid_obstacles(
+    agent: &Agent,
+    obstacles: impl IntoIterator<Item = Neighbour>,
+    look_ahead: f32,
+) -> Vec2 {
+    let Some(ahead) = agent.velocity.try_normalize() else {
+        return Vec2::ZERO;
+    };
+
+    let closest = obstacles
+        .into_iter()
+        .filter_map(|obstacle| {
+            let along = obstacle.offset.dot(ahead);
+            let lateral = obstacle.offset - along * ahead;
+            let in_path = lateral.length() < obstacle.radius + agent.radius;
+            (along > 0.0 && along < look_ahead && in_path).then_some((along, lateral))
+        })
+        .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+    let Some((along, lateral)) = closest else {
+        return Vec2::ZERO;
+    };
+    // Obstacles straight ahead are passed on the left.
+    let away = (-lateral).try_normalize().unwrap_or(ahead.perp());
+    away * agent.max_acceleration * (1.0 - along / look_ahead)
+}
+
+/// Steer sideways away from a surface that the agent, heading in the unit
+/// length direction `ahead`, would run into `distance` ahead. `normal` points
+/// out of the surface. Surfaces straight ahead are passed on the left.
+pub fn avoid_surface(
+    agent: &Agent,
+    ahead: Vec2,
+    distance: f32,
+    normal: Vec2,
+    look_ahead: f32,
+) -> Vec2 {
+    let lateral = normal - normal.dot(ahead) * ahead;
+    let away = lateral.try_normalize().unwrap_or(ahead.perp());
+    away * agent.max_acceleration * (1.0 - distance / look_ahead).max(0.0)
+}
+
+/// Move away from `neighbours`, the closer ones pushing harder.
+pub fn separation(agent: &Agent, neighbours: &[Neighbour]) -> Vec2 {
+    let push: Vec2 = neighbours
+        .iter()
+        .map(|neighbour| -neighbour.offset / neighbour.offset.length_squared().max(1.0))
+        .sum();
+    if push == Vec2::ZERO {
+        return Vec2::ZERO;
+    }
+
+    seek(agent, push)
+}
+
+/// Match the average velocity of `neighbours`.
+pub fn alignment(agent: &Agent, neighbours: &[Neighbour]) -> Vec2 {
+    if neighbours.is_empty() {
+        return Vec2::ZERO;
+    }
+
+    let velocity = neighbours.iter().map(|n| n.velocity).sum::<Vec2>() / neighbours.len() as f32;
+    steer_towards(agent, velocity)
+}
+
+/// Go towards the centre of `neighbours`.
+pub fn cohesion(agent: &Agent, neighbours: &[Neighbour]) -> Vec2 {
+    if neighbours.is_empty() {
+        return Vec2::ZERO;
+    }
+
+    let centre = neighbours.iter().map(|n| n.offset).sum::<Vec2>() / neighbours.len() as f32;
+    seek(agent, centre)
+}
+
+/// Weighted sum of the accelerations of several behaviours, limited to what
+/// the agent can do.
+pub fn blend(agent: &Agent, weighted: impl IntoIterator<Item = (f32, Vec2)>) -> Vec2 {
+    weighted
+        .into_iter()
+        .map(|(weight, acceleration)| weight * acceleration)
+        .sum::<Vec2>()
+        .clamp_length_max(agent.max_acceleration)
+}
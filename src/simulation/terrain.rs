@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, fmt, fs};
+
+use bevy::{
+    asset::{io::file::FileAssetReader, RenderAssetUsages},
+    image::{CompressedImageFormats, ImageFormat, ImageSampler, ImageType},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{scenario::ScenarioError, *};
+
+/// Kind of habitat at a point of the world.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Biome {
+    Meadow,
+    Forest,
+    Water,
+    Rock,
+}
+
+/// How a biome changes what happens within it, as factors on the rates
+/// elsewhere.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct BiomeEffects {
+    /// Factor on the maximum speed of units.
+    pub speed: f32,
+    /// Factor on the fullness that units lose per unit travelled.
+    pub drain: f32,
+    /// Factor on the rate that bushes grow berries back at.
+    pub growth: f32,
+}
+
+/// The biome of every point of the world, read from a map image that is
+/// stretched over the world's bounds. Without a map the whole world is meadow.
+#[derive(Resource)]
+pub struct Terrain {
+    bounds: scenario::Bounds,
+    width: u32,
+    height: u32,
+    /// Row by row, starting at the top of the map.
+    biomes: Vec<Biome>,
+    effects: BTreeMap<Biome, BiomeEffects>,
+    /// Map image, relative to the assets directory.
+    pub map: Option<String>,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 4] = [Biome::Meadow, Biome::Forest, Biome::Water, Biome::Rock];
+
+    /// Color of the biome in a map, unless the scenario gives a palette.
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            Biome::Meadow => [126, 200, 80],
+            Biome::Forest => [34, 110, 50],
+            Biome::Water => [50, 110, 200],
+            Biome::Rock => [140, 140, 140],
+        }
+    }
+
+    /// Effects of the biome, unless the scenario says otherwise.
+    pub fn effects(self) -> BiomeEffects {
+        let (speed, drain, growth) = match self {
+            Biome::Meadow => (1.0, 1.0, 1.0),
+            Biome::Forest => (0.75, 1.2, 1.5),
+            Biome::Water => (0.4, 2.0, 0.0),
+            Biome::Rock => (0.8, 1.5, 0.25),
+        };
+
+        BiomeEffects {
+            speed,
+            drain,
+            growth,
+        }
+    }
+}
+
+impl fmt::Display for Biome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Biome::Meadow => "meadow",
+            Biome::Forest => "forest",
+            Biome::Water => "water",
+            Biome::Rock => "rock",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl BiomeEffects {
+    /// Name of the first field that is out of range.
+    pub fn invalid_field(&self) -> Option<&'static str> {
+        [
+            ("speed", self.speed),
+            ("drain", self.drain),
+            ("growth", self.growth),
+        ]
+        .into_iter()
+        .find(|(_, value)| !value.is_finite() || *value < 0.0)
+        .map(|(field, _)| field)
+    }
+}
+
+impl Terrain {
+    /// Terrain that is meadow everywhere.
+    pub fn uniform(bounds: scenario::Bounds) -> Self {
+        Terrain {
+            bounds,
+            width: 1,
+            height: 1,
+            biomes: vec![Biome::Meadow],
+            effects: BTreeMap::from([(Biome::Meadow, Biome::Meadow.effects())]),
+            map: None,
+        }
+    }
+
+    /// Read the map of `config` and give every pixel the biome whose color in
+    /// the palette is closest to its own.
+    pub fn load(
+        config: &scenario::TerrainConfig,
+        bounds: scenario::Bounds,
+    ) -> Result<Self, ScenarioError> {
+        let path = FileAssetReader::get_base_path()
+            .join("assets")
+            .join(&config.map);
+        let bytes = fs::read(&path).map_err(|err| ScenarioError::Io(path.clone(), err))?;
+        let image = Image::from_buffer(
+            &bytes,
+            ImageType::Format(ImageFormat::Png),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+            RenderAssetUsages::default(),
+        )
+        .map_err(|err| ScenarioError::Invalid {
+            field: "world.terrain.map".to_string(),
+            reason: format!("{}: {err}", path.display()),
+        })?;
+
+        let palette = config.palette();
+        let (width, height) = (image.width(), image.height());
+        let biomes = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let color = image
+                    .get_color_at(x, y)
+                    .map_err(|err| ScenarioError::Invalid {
+                        field: "world.terrain.map".to_string(),
+                        reason: format!("{}: {err}", path.display()),
+                    })?
+                    .to_srgba()
+                    .to_u8_array();
+                Ok(closest(&palette, [color[0], color[1], color[2]]))
+            })
+            .collect::<Result<_, ScenarioError>>()?;
+
+        let effects = Biome::ALL
+            .into_iter()
+            .map(|biome| (biome, config.effects(biome)))
+            .collect();
+
+        Ok(Terrain {
+            bounds,
+            width,
+            height,
+            biomes,
+            effects,
+            map: Some(config.map.clone()),
+        })
+    }
+
+    pub fn biome_at(&self, position: Vec2) -> Biome {
+        let uv = (position - self.bounds.min()) / self.bounds.size();
+        // Images start at the top, the world at the bottom.
+        let x = (uv.x * self.width as f32).clamp(0.0, (self.width - 1) as f32) as u32;
+        let y = ((1.0 - uv.y) * self.height as f32).clamp(0.0, (self.height - 1) as f32) as u32;
+
+        self.biomes[(y * self.width + x) as usize]
+    }
+
+    pub fn effects_at(&self, position: Vec2) -> BiomeEffects {
+        self.effects[&self.biome_at(position)]
+    }
+
+    pub fn bounds(&self) -> scenario::Bounds {
+        self.bounds
+    }
+}
+
+fn closest(palette: &[(Biome, [u8; 3])], color: [u8; 3]) -> Biome {
+    let distance = |other: &[u8; 3]| -> i32 {
+        color
+            .iter()
+            .zip(other)
+            .map(|(a, b)| (*a as i32 - *b as i32).pow(2))
+            .sum()
+    };
+
+    palette
+        .iter()
+        .min_by_key(|(_, other)| distance(other))
+        .map_or(Biome::Meadow, |(biome, _)| *biome)
+}
+
+/// Build the terrain of the scenario that is being simulated.
+pub fn load_terrain(mut commands: Commands, scenario: Res<Scenario>) {
+    let bounds = scenario.world.bounds;
+    let terrain = match &scenario.world.terrain {
+        // Scenarios are validated when loaded, so this only fails if the map
+        // has changed since.
+        Some(config) => Terrain::load(config, bounds).unwrap_or_else(|err| {
+            error!("Failed to load terrain: {err}");
+            Terrain::uniform(bounds)
+        }),
+        None => Terrain::uniform(bounds),
+    };
+
+    commands.insert_resource(terrain);
+}
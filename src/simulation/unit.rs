@@ -1,53 +1,364 @@
-pub mod fernworm;
-pub mod zyrthid;
+use crate::simulation::{species::SpeciesId, *};
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+use serde::{Deserialize, Serialize};
 
-use crate::simulation::*;
-use bevy::prelude::*;
+/// The species of a unit.
+#[derive(Component, Copy, Clone, PartialEq, Eq)]
+pub struct UnitType(pub SpeciesId);
 
-#[derive(Component, Copy, Clone)]
-pub enum UnitType {
-    Fernworm,
-    Zyrthid,
+/// Where a unit comes from. Ids are unique within a simulation, unlike
+/// entities, and survive snapshots.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Lineage {
+    pub id: u64,
+    pub parent: Option<u64>,
+    /// Zero for units spawned by the scenario.
+    pub generation: u32,
+    /// Tick the unit was spawned at.
+    pub born: u64,
+}
+
+/// Remains of a dead unit. Scavengers eat its biomass, and the rest decays
+/// into nutrients that berries grow from.
+#[derive(Component)]
+pub struct Corpse {
+    pub unit: UnitType,
+    /// Fullness that scavengers can still gain from it.
+    pub biomass: f32,
+    /// Decayed biomass that has not grown into a berry yet.
+    pub nutrients: f32,
 }
 
 pub struct CorpseData {
     pub unit: UnitType,
     pub translation: Vec3,
     pub rotation: f32,
+    pub biomass: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause {
+    Starvation,
+    Predation,
 }
 
 #[derive(Event)]
 pub struct DeathEvent {
     pub entity: Entity,
+    pub unit: UnitType,
+    pub cause: DeathCause,
     pub corpse: Option<unit::CorpseData>,
 }
 
-pub fn kill_units(
+/// Sent for every unit born from reproduction.
+#[derive(Event)]
+pub struct BirthEvent {
+    pub unit: UnitType,
+}
+
+/// Components that make up the simulation state of a unit.
+#[derive(Bundle)]
+pub struct UnitBundle {
+    pub unit: UnitType,
+    pub rotation: motion::Rotation,
+    pub transform: Transform,
+    pub moving_body: motion::MovingBody,
+    pub hunger: hunger::Hunger,
+    pub target_point: motion::TargetPoint,
+    pub fertility: reproduction::Fertility,
+    pub genome: genome::Genome,
+    pub brain: brain::Brain,
+    pub target: brain::Target,
+    pub route: obstacle::Route,
+    pub senses: senses::Senses,
+    pub lineage: Lineage,
+}
+
+/// What a new unit starts out with.
+pub struct UnitSpawn {
+    pub species: SpeciesId,
+    pub position: Vec2,
+    /// Gives the unit's body and metabolism.
+    pub genome: genome::Genome,
+    pub fullness: f32,
+    pub brain: brain::Brain,
+    pub senses: senses::Senses,
+    pub lineage: Lineage,
+}
+
+/// Spawn a unit whose body and metabolism are given by its genome.
+pub fn spawn_unit(commands: &mut Commands, registry: &SpeciesRegistry, spawn: UnitSpawn) -> Entity {
+    let UnitSpawn {
+        species,
+        position,
+        genome,
+        fullness,
+        brain,
+        senses,
+        lineage,
+    } = spawn;
+    let init_pos = position.extend(registry.get(species).depth);
+
+    commands
+        .spawn((
+            SimulationComponent,
+            UnitBundle {
+                unit: UnitType(species),
+                rotation: motion::Rotation(0.0),
+                transform: Transform {
+                    translation: init_pos,
+                    scale: Vec3::splat(genome.body_scale),
+                    ..default()
+                },
+                moving_body: motion::MovingBody {
+                    curr_velocity: Vec3::ZERO,
+                    max_speed: genome.max_speed,
+                    curr_acceleration: Vec3::ZERO,
+                    max_acceleration: genome.max_acceleration,
+                },
+                hunger: hunger::Hunger {
+                    curr_fullness: fullness.min(genome.max_fullness),
+                    max_fullness: genome.max_fullness,
+                    drain_per_unit_traveled: genome.drain_per_unit_traveled,
+                    last_sampled_pos: init_pos,
+                },
+                target_point: motion::TargetPoint(None),
+                fertility: reproduction::Fertility::default(),
+                genome,
+                brain,
+                target: brain::Target::default(),
+                route: obstacle::Route::default(),
+                senses,
+                lineage,
+            },
+        ))
+        .id()
+}
+
+/// Bounding box of the mouth of a unit.
+fn mouth(species: &Species, transform: &Transform, rotation: &motion::Rotation) -> Aabb2d {
+    let mouth_offset = transform.scale.y * species.mouth.offset;
+    let mouth_translation = transform.translation
+        + Vec3::new(
+            -rotation.0.sin() * mouth_offset,
+            rotation.0.cos() * mouth_offset,
+            0.0,
+        );
+    let mouth_size =
+        transform.scale.truncate() * Vec2::new(species.mouth.size.width, species.mouth.size.height);
+
+    Aabb2d::new(mouth_translation.truncate(), mouth_size)
+}
+
+fn eat(hunger: &mut hunger::Hunger, fullness_gain: f32) {
+    let new_fullness = hunger.curr_fullness + fullness_gain;
+    if new_fullness > hunger.max_fullness {
+        hunger.curr_fullness = hunger.max_fullness;
+    } else {
+        hunger.curr_fullness = new_fullness;
+    }
+}
+
+/// Eat as much of `biomass` as there is room for and return what is left.
+fn eat_until_full(hunger: &mut hunger::Hunger, biomass: f32) -> f32 {
+    let eaten = biomass
+        .min(hunger.max_fullness - hunger.curr_fullness)
+        .max(0.0);
+    hunger.curr_fullness += eaten;
+
+    biomass - eaten
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn eat_berries(
     mut commands: Commands,
-    mut events: EventReader<DeathEvent>,
-    asset_server: Res<AssetServer>,
+    mut game_data: ResMut<SimData>,
+    index: Res<spatial::SpatialIndex>,
+    berry_query: Query<(&Transform, &berry::Berry), Without<UnitType>>,
+    mut bush_query: Query<&mut berry::Bush>,
+    mut unit_query: Query<(
+        &Transform,
+        &UnitType,
+        &motion::Rotation,
+        &mut hunger::Hunger,
+    )>,
+    registry: Res<SpeciesRegistry>,
+    scenario: Res<Scenario>,
 ) {
+    // So that no two units can eat the same berry during the same tick.
+    let mut eaten = EntityHashSet::default();
+
+    for (unit_transform, unit, rotation, mut hunger) in unit_query.iter_mut() {
+        let species = registry.get(unit.0);
+        if !species.diet.berries {
+            continue;
+        }
+
+        let mouth = mouth(species, unit_transform, rotation);
+        let reach = mouth.half_size().length();
+        for shift in scenario
+            .world
+            .shifts(mouth.center(), reach + index.berries.max_radius())
+        {
+            for candidate in index.berries.overlapping(mouth.center() + shift, reach) {
+                let Ok((berry_transform, berry)) = berry_query.get(candidate.entity) else {
+                    continue;
+                };
+                let berry_size = berry_transform.scale.truncate()
+                    * Vec2::new(BERRY_RENDER_WIDTH, BERRY_RENDER_HEIGHT);
+                let berry_box =
+                    Aabb2d::new(berry_transform.translation.truncate() - shift, berry_size);
+
+                if berry_box.intersects(&mouth) && eaten.insert(candidate.entity) {
+                    commands.entity(candidate.entity).despawn();
+                    match berry.bush {
+                        Some(bush) => {
+                            if let Ok(mut bush) = bush_query.get_mut(bush) {
+                                bush.berries -= 1;
+                            }
+                        }
+                        None => game_data.num_berries -= 1,
+                    }
+
+                    eat(&mut hunger, berry.fullness);
+                }
+            }
+        }
+    }
+}
+
+pub fn eat_prey(
+    index: Res<spatial::SpatialIndex>,
+    prey_query: Query<(&Transform, &motion::Rotation), With<UnitType>>,
+    mut predator_query: Query<(
+        Entity,
+        &Transform,
+        &UnitType,
+        &motion::Rotation,
+        &mut hunger::Hunger,
+    )>,
+    mut event: EventWriter<unit::DeathEvent>,
+    registry: Res<SpeciesRegistry>,
+    scenario: Res<Scenario>,
+) {
+    // So that no two units can eat the same prey during the same tick.
+    let mut eaten = EntityHashSet::default();
+
+    for (predator_entity, predator_transform, predator_unit, rotation, mut hunger) in
+        predator_query.iter_mut()
+    {
+        let predator_species = registry.get(predator_unit.0);
+        if predator_species.diet.prey.is_empty() {
+            continue;
+        }
+
+        let mouth = mouth(predator_species, predator_transform, rotation);
+        let reach = mouth.half_size().length();
+        for shift in scenario
+            .world
+            .shifts(mouth.center(), reach + index.units.max_radius())
+        {
+            for candidate in index.units.overlapping(mouth.center() + shift, reach) {
+                if candidate.entity == predator_entity
+                    || !predator_species.diet.prey.contains(&candidate.data.species)
+                {
+                    continue;
+                }
+                let Ok((prey_transform, prey_rotation)) = prey_query.get(candidate.entity) else {
+                    continue;
+                };
+
+                let prey_species = registry.get(candidate.data.species);
+                let prey_size = prey_transform.scale.truncate()
+                    * Vec2::new(prey_species.body.width, prey_species.body.height);
+                let prey = Aabb2d::new(prey_transform.translation.truncate() - shift, prey_size);
+
+                if prey.intersects(&mouth) && eaten.insert(candidate.entity) {
+                    // Whatever the predator has no room for is left behind.
+                    let leftover = eat_until_full(&mut hunger, prey_species.nutrition);
+                    let unit = UnitType(candidate.data.species);
+                    event.write(unit::DeathEvent {
+                        entity: candidate.entity,
+                        unit,
+                        cause: DeathCause::Predation,
+                        corpse: (leftover > 0.0).then_some(unit::CorpseData {
+                            unit,
+                            translation: prey_transform.translation,
+                            rotation: prey_rotation.0,
+                            biomass: leftover,
+                        }),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Let units with carrion in their diet eat from corpses in reach of their
+/// mouths. Corpses that are eaten up are despawned.
+pub fn eat_carrion(
+    mut commands: Commands,
+    index: Res<spatial::SpatialIndex>,
+    mut corpse_query: Query<(&Transform, &mut Corpse)>,
+    mut unit_query: Query<(
+        &Transform,
+        &UnitType,
+        &motion::Rotation,
+        &mut hunger::Hunger,
+    )>,
+    registry: Res<SpeciesRegistry>,
+    scenario: Res<Scenario>,
+) {
+    for (unit_transform, unit, rotation, mut hunger) in unit_query.iter_mut() {
+        let species = registry.get(unit.0);
+        if !species.diet.carrion {
+            continue;
+        }
+
+        let mouth = mouth(species, unit_transform, rotation);
+        let reach = mouth.half_size().length();
+        for shift in scenario
+            .world
+            .shifts(mouth.center(), reach + index.corpses.max_radius())
+        {
+            for candidate in index.corpses.overlapping(mouth.center() + shift, reach) {
+                let Ok((corpse_transform, mut corpse)) = corpse_query.get_mut(candidate.entity)
+                else {
+                    continue;
+                };
+                // Eaten up by another unit earlier in this tick.
+                if corpse.biomass <= 0.0 {
+                    continue;
+                }
+
+                let body = registry.get(corpse.unit.0).body;
+                let corpse_size =
+                    corpse_transform.scale.truncate() * Vec2::new(body.width, body.height);
+                let corpse_box =
+                    Aabb2d::new(corpse_transform.translation.truncate() - shift, corpse_size);
+                if corpse_box.intersects(&mouth) {
+                    corpse.biomass = eat_until_full(&mut hunger, corpse.biomass);
+                    if corpse.biomass <= 0.0 {
+                        commands.entity(candidate.entity).despawn();
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn kill_units(mut commands: Commands, mut events: EventReader<DeathEvent>) {
     for event in events.read() {
-        // Despawn the living sprite.
+        // Despawn the living unit.
         commands.entity(event.entity).despawn();
 
         if let Some(corpse) = &event.corpse {
-            let custom_size = match corpse.unit {
-                UnitType::Fernworm => Vec2::new(FERNWORM_RENDER_WIDTH, FERNWORM_RENDER_HEIGHT),
-                UnitType::Zyrthid => Vec2::new(ZYRTHID_RENDER_WIDTH, ZYRTHID_RENDER_HEIGHT),
-            };
-
             commands.spawn((
                 SimulationComponent,
-                Sprite {
-                    image: match corpse.unit {
-                        unit::UnitType::Fernworm => {
-                            asset_server.load("sprites/fernworm_corpse.png")
-                        }
-                        unit::UnitType::Zyrthid => asset_server.load("sprites/zyrthid_corpse.png"),
-                    },
-                    custom_size: Some(custom_size),
-                    ..default()
+                Corpse {
+                    unit: corpse.unit,
+                    biomass: corpse.biomass,
+                    nutrients: 0.0,
                 },
                 Transform {
                     translation: corpse.translation,
@@ -5,4 +5,6 @@ pub enum AppState {
     #[default]
     Menu,
     Simulation,
+    /// Playing back a recorded run.
+    Replay,
 }